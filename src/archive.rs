@@ -0,0 +1,169 @@
+use crate::{prune_tree, sort_tree, ScanError, Special, TreeNode};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+// zip and tar files as trees: the entry listing is read, nothing gets extracted
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+/// going by the name, `.tar.gz` before `.gz` so a plain gzip file isn't mistaken for a tarball
+pub fn archive_kind(name: &str) -> Option<ArchiveKind> {
+    let name = name.to_lowercase();
+    let ends = |suffixes: &[&str]| suffixes.iter().any(|s| name.ends_with(s));
+    if ends(&[".tar.gz", ".tgz", ".crate"]) {
+        Some(ArchiveKind::TarGz)
+    } else if ends(&[".tar.zst", ".tzst"]) {
+        Some(ArchiveKind::TarZst)
+    } else if ends(&[".tar"]) {
+        Some(ArchiveKind::Tar)
+    } else if ends(&[".zip", ".jar", ".whl", ".vsix", ".nupkg"]) {
+        // jars, wheels and extensions are zips with another name
+        Some(ArchiveKind::Zip)
+    } else {
+        None
+    }
+}
+
+/// the archive's entries as a tree under a root named after the file
+pub fn read_archive(path: &Path, kind: ArchiveKind) -> io::Result<TreeNode> {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let mut root = TreeNode { name, is_dir: true, ..Default::default() };
+    let file = BufReader::new(File::open(path)?);
+    match kind {
+        ArchiveKind::Zip => read_zip(file, &mut root)?,
+        ArchiveKind::Tar => read_tar(file, &mut root)?,
+        ArchiveKind::TarGz => read_tar(flate2::read::GzDecoder::new(file), &mut root)?,
+        ArchiveKind::TarZst => {
+            let decoder = ruzstd::decoding::StreamingDecoder::new(file).map_err(io::Error::other)?;
+            read_tar(decoder, &mut root)?
+        }
+    }
+    sort_tree(&mut root);
+    Ok(root)
+}
+
+fn read_zip<R: Read + io::Seek>(file: R, root: &mut TreeNode) -> io::Result<()> {
+    let mut zip = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+    for i in 0..zip.len() {
+        // raw, so nothing gets decompressed just to list it
+        let entry = zip.by_index_raw(i).map_err(io::Error::other)?;
+        let name = entry.name().map_err(io::Error::other)?.to_string();
+        let is_dir = entry.is_dir();
+        let node = TreeNode {
+            is_dir,
+            size: if is_dir { None } else { Some(entry.size()) },
+            mode: entry.unix_mode(),
+            mtime: entry.last_modified().and_then(|t| {
+                use chrono::{Local, NaiveDate, TimeZone};
+                // zip times have no zone, they're whatever the zipping machine's clock said
+                let date = NaiveDate::from_ymd_opt(t.year() as i32, t.month() as u32, t.day() as u32)?;
+                let time = date.and_hms_opt(t.hour() as u32, t.minute() as u32, t.second() as u32)?;
+                Local.from_local_datetime(&time).earliest().map(|t| t.timestamp())
+            }),
+            ..Default::default()
+        };
+        insert(root, &name, node);
+    }
+    Ok(())
+}
+
+fn read_tar<R: Read>(reader: R, root: &mut TreeNode) -> io::Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let kind = header.entry_type();
+        // pax and gnu long-name records describe the next entry, they aren't entries themselves
+        if !(kind.is_file() || kind.is_dir() || kind.is_symlink() || kind.is_hard_link()) {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        let is_dir = kind.is_dir();
+        // tar keeps the permission bits only, the type bits make the long listing's first letter right
+        let type_bits = if is_dir {
+            0o040000
+        } else if kind.is_symlink() {
+            0o120000
+        } else {
+            0o100000
+        };
+        let node = TreeNode {
+            is_dir,
+            size: if is_dir { None } else { header.size().ok() },
+            mode: header.mode().ok().map(|m| (m & 0o7777) | type_bits),
+            mtime: header.mtime().ok().map(|t| t as i64),
+            owner: header.username().ok().flatten().map(str::to_string),
+            group: header.groupname().ok().flatten().map(str::to_string),
+            special: if kind.is_symlink() { Some(Special::Symlink) } else { None },
+            ..Default::default()
+        };
+        insert(root, &name, node);
+    }
+    Ok(())
+}
+
+// archives don't have to list a folder before its contents, or at all, so missing ones get made up on the way
+fn insert(root: &mut TreeNode, path: &str, mut node: TreeNode) {
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty() && *p != ".").collect();
+    let Some((last, dirs)) = parts.split_last() else { return };
+    let mut parent = root;
+    for part in dirs {
+        let i = match parent.children.iter().position(|c| c.is_dir && c.name == *part) {
+            Some(i) => i,
+            None => {
+                parent.children.push(TreeNode { name: part.to_string(), is_dir: true, ..Default::default() });
+                parent.children.len() - 1
+            }
+        };
+        parent = &mut parent.children[i];
+    }
+    node.name = last.to_string();
+    // a folder entry that comes after its made-up stand-in fills in the details
+    if let Some(existing) = parent.children.iter_mut().find(|c| c.is_dir && node.is_dir && c.name == node.name) {
+        existing.mode = node.mode;
+        existing.mtime = node.mtime;
+        existing.owner = node.owner;
+        existing.group = node.group;
+        return;
+    }
+    parent.children.push(node);
+}
+
+/// --archives: archives found in a walked tree get their entries as children, the archive itself stays a file.
+/// `max_depth` and the filters apply inside them like anywhere else
+pub fn expand_archives(tree: &mut TreeNode, root: &Path, max_depth: Option<usize>, show_hidden: bool, filter: Option<&str>) {
+    let mut jobs = Vec::new();
+    collect_archives(tree, root.to_path_buf(), max_depth, &mut jobs);
+    jobs.into_par_iter().for_each(|(node, path, kind, depth)| match read_archive(&path, kind) {
+        Ok(listing) => node.children = prune_tree(listing, depth, show_hidden, filter).children,
+        // shows up like a folder that couldn't be read, in the tree and in the summary
+        Err(e) => node.error = Some(ScanError::from_io(&e)),
+    });
+}
+
+type ArchiveJob<'a> = (&'a mut TreeNode, PathBuf, ArchiveKind, Option<usize>);
+
+// `depth` is what build_tree got for `node`, an archive inside opens as far as a folder in its place would
+fn collect_archives<'a>(node: &'a mut TreeNode, path: PathBuf, depth: Option<usize>, jobs: &mut Vec<ArchiveJob<'a>>) {
+    // folders at the depth limit are listed but not opened, same for archives
+    if depth == Some(0) {
+        return;
+    }
+    let depth = depth.map(|d| d - 1);
+    for child in node.children.iter_mut() {
+        let child_path = path.join(&child.name);
+        if child.is_dir {
+            collect_archives(child, child_path, depth, jobs);
+        } else if let Some(kind) = archive_kind(&child.name).filter(|_| child.special.is_none()) {
+            jobs.push((child, child_path, kind, depth));
+        }
+    }
+}
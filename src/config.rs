@@ -0,0 +1,79 @@
+use crate::{record_error, ScanError, ScanErrorKind};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// user config, lives at ~/.config/canopy/config.toml (or %APPDATA%\canopy\config.toml)
+// point CANOPY_CONFIG somewhere else if u want a different one
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub icons: IconConfig,
+    pub sizes: SizeConfig,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct SizeConfig {
+    /// powers of 1000 instead of 1024
+    pub si: bool,
+    /// sizes like "10K", green below medium, red from large up
+    pub medium: Option<String>,
+    pub large: Option<String>,
+    /// files above this get tagged [BIG]
+    pub big_file: Option<String>,
+    /// scale everything to the largest file in the tree
+    pub relative: bool,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct IconConfig {
+    /// theme used when --icons isn't given
+    pub theme: Option<String>,
+    pub text: IconOverrides,
+    pub nerd: IconOverrides,
+    pub emoji: IconOverrides,
+}
+
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct IconOverrides {
+    /// extension (without the dot, lowercase) -> icon
+    pub extensions: HashMap<String, String>,
+    /// exact file or folder name -> icon
+    pub filenames: HashMap<String, String>,
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(p) = std::env::var_os("CANOPY_CONFIG") {
+        return Some(PathBuf::from(p));
+    }
+    if cfg!(windows) {
+        if let Some(appdata) = std::env::var_os("APPDATA") {
+            return Some(PathBuf::from(appdata).join("canopy").join("config.toml"));
+        }
+    }
+    if let Some(xdg) = std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(xdg).join("canopy").join("config.toml"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("canopy").join("config.toml"))
+}
+
+pub fn load_config() -> Config {
+    let Some(path) = config_path() else {
+        return Config::default();
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(t) => t,
+        // no config is totally fine, most people won't have one
+        Err(_) => return Config::default(),
+    };
+    match toml::from_str(&text) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            record_error(&path, ScanError { kind: ScanErrorKind::Io, message: format!("ignoring broken config: {}", e) });
+            Config::default()
+        }
+    }
+}
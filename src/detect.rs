@@ -0,0 +1,216 @@
+use crate::{collect_files, TreeNode};
+use rayon::prelude::*;
+use std::io::Read;
+use std::path::Path;
+
+// --detect: what a file is by its first bytes instead of its name. the labels map to
+// registry kinds in icons.rs, this side only knows the byte patterns
+
+// enough for tar's `ustar` at 257, a long shebang line and the pe header, which linkers put in the first kilobyte
+const SNIFF_LEN: usize = 1024;
+
+/// a short label like `elf`, `png` or `python`, `text` / `binary` when nothing more specific matched
+pub fn sniff(path: &Path) -> Option<&'static str> {
+    let mut buf = [0u8; SNIFF_LEN];
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut len = 0;
+    // read can come back short on pipes and network drives, keep going until the buffer's full or the file ends
+    while len < SNIFF_LEN {
+        match file.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return None,
+        }
+    }
+    // empty files could be anything
+    if len == 0 {
+        return None;
+    }
+    Some(classify(&buf[..len]))
+}
+
+fn classify(b: &[u8]) -> &'static str {
+    let at = |offset: usize, magic: &[u8]| b.get(offset..offset + magic.len()) == Some(magic);
+    let starts = |magic: &[u8]| at(0, magic);
+    if starts(b"\x7fELF") {
+        "elf"
+    } else if starts(b"MZ") && is_pe(b) {
+        "pe"
+    } else if [b"\xfe\xed\xfa\xce", b"\xfe\xed\xfa\xcf", b"\xce\xfa\xed\xfe", b"\xcf\xfa\xed\xfe"].iter().any(|m| starts(*m)) {
+        "mach-o"
+    } else if starts(b"\xca\xfe\xba\xbe") {
+        // java classes share cafebabe with fat mach-o, a fat header counts arches (small), a class has its version there
+        match b.get(4..8) {
+            Some(n) if u32::from_be_bytes([n[0], n[1], n[2], n[3]]) < 45 => "mach-o",
+            _ => "class",
+        }
+    } else if starts(b"\0asm") {
+        "wasm"
+    } else if starts(b"\x89PNG\r\n\x1a\n") {
+        "png"
+    } else if starts(b"\xff\xd8\xff") {
+        "jpeg"
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        "gif"
+    } else if starts(b"RIFF") && at(8, b"WEBP") {
+        "webp"
+    } else if starts(b"RIFF") && at(8, b"WAVE") {
+        "wav"
+    } else if starts(b"BM") && b.len() >= 26 && at(6, b"\0\0\0\0") {
+        "bmp"
+    } else if starts(b"\0\0\x01\0") {
+        "ico"
+    } else if starts(b"II*\0") || starts(b"MM\0*") {
+        "tiff"
+    } else if starts(b"%PDF-") {
+        "pdf"
+    } else if starts(b"PK\x03\x04") || starts(b"PK\x05\x06") {
+        "zip"
+    } else if starts(b"\x1f\x8b") {
+        "gzip"
+    } else if starts(b"\xfd7zXZ\0") {
+        "xz"
+    } else if starts(b"BZh") {
+        "bzip2"
+    } else if starts(b"7z\xbc\xaf\x27\x1c") {
+        "7z"
+    } else if starts(b"\x28\xb5\x2f\xfd") {
+        "zstd"
+    } else if starts(b"Rar!\x1a\x07") {
+        "rar"
+    } else if at(257, b"ustar") {
+        "tar"
+    } else if starts(b"SQLite format 3\0") {
+        "sqlite"
+    } else if starts(b"ID3") || starts(b"\xff\xfb") || starts(b"\xff\xf3") || starts(b"\xff\xf2") {
+        "mp3"
+    } else if starts(b"fLaC") {
+        "flac"
+    } else if starts(b"OggS") {
+        "ogg"
+    } else if at(4, b"ftypM4A") {
+        "m4a"
+    } else if at(4, b"ftyp") {
+        "mp4"
+    } else if starts(b"\x1a\x45\xdf\xa3") {
+        "mkv"
+    } else if starts(b"\0\x01\0\0\0") {
+        "ttf"
+    } else if starts(b"OTTO") {
+        "otf"
+    } else if starts(b"wOFF") {
+        "woff"
+    } else if starts(b"wOF2") {
+        "woff2"
+    } else if starts(b"#!") {
+        shebang(b)
+    } else if is_text(b) {
+        "text"
+    } else {
+        "binary"
+    }
+}
+
+// `MZ` is only the dos stub and plenty of text starts with it, the pe header is where 0x3c points
+fn is_pe(b: &[u8]) -> bool {
+    let Some(&[a, b1, c, d]) = b.get(0x3c..0x40) else { return false };
+    let offset = u32::from_le_bytes([a, b1, c, d]) as usize;
+    offset.checked_add(4).and_then(|end| b.get(offset..end)) == Some(b"PE\0\0")
+}
+
+/// `#!/usr/bin/env python3` and `#!/bin/bash` both come down to the interpreter's name
+fn shebang(b: &[u8]) -> &'static str {
+    let line = b[2..].split(|&c| c == b'\n').next().unwrap_or_default();
+    let line = String::from_utf8_lossy(line);
+    let mut words = line.split_whitespace();
+    let mut program = words.next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+    if program == "env" {
+        // `env -S python3 -u`, the flags before the program don't matter
+        program = words.find(|w| !w.starts_with('-')).unwrap_or_default();
+    }
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match program {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "fish" | "ash" => "shell",
+        "python" | "pypy" => "python",
+        "perl" => "perl",
+        "ruby" => "ruby",
+        "node" | "deno" | "bun" => "javascript",
+        "php" => "php",
+        "lua" | "luajit" => "lua",
+        "pwsh" | "powershell" => "powershell",
+        _ => "script",
+    }
+}
+
+// no NUL bytes and valid utf-8, allowing a character cut in half at the end of the sample
+fn is_text(b: &[u8]) -> bool {
+    if b.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(b) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// sniff every file in the tree, spread over all cores
+pub fn fill_detected(tree: &mut TreeNode, root: &Path) {
+    let mut jobs = Vec::new();
+    collect_files(tree, root.to_path_buf(), &mut jobs);
+    // opening a fifo blocks until someone writes to it
+    jobs.retain(|(node, _)| node.special.is_none());
+    jobs.into_par_iter().for_each(|(node, path)| {
+        node.detected = sniff(&path).map(str::to_string);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pe(offset: usize) -> Vec<u8> {
+        let mut b = vec![0u8; offset + 64];
+        b[..2].copy_from_slice(b"MZ");
+        b[0x3c..0x40].copy_from_slice(&(offset as u32).to_le_bytes());
+        b[offset..offset + 4].copy_from_slice(b"PE\0\0");
+        b
+    }
+
+    #[test]
+    fn pe_needs_its_header() {
+        assert_eq!(classify(&pe(0x80)), "pe");
+        assert_eq!(classify(&pe(0x118)), "pe");
+        assert_eq!(classify(b"MZ is a nice abbreviation\n"), "text");
+        let mut stub_only = pe(0x80);
+        stub_only[0x80..0x84].copy_from_slice(b"NE\0\0");
+        assert_eq!(classify(&stub_only), "binary");
+        // an offset pointing past the sample or overflowing isn't a pe either
+        let mut far = pe(0x80);
+        far[0x3c..0x40].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(classify(&far), "binary");
+    }
+
+    #[test]
+    fn magic_numbers() {
+        assert_eq!(classify(b"\x7fELF\x02\x01\x01"), "elf");
+        assert_eq!(classify(b"\x89PNG\r\n\x1a\n\0\0"), "png");
+        assert_eq!(classify(b"%PDF-1.7\n"), "pdf");
+        assert_eq!(classify(b"PK\x03\x04\x14\0"), "zip");
+        assert_eq!(classify(b"\xca\xfe\xba\xbe\0\0\0\x02"), "mach-o");
+        assert_eq!(classify(b"\xca\xfe\xba\xbe\0\0\0\x34"), "class");
+        let mut tar = vec![0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(classify(&tar), "tar");
+    }
+
+    #[test]
+    fn scripts_and_text() {
+        assert_eq!(classify(b"#!/usr/bin/env -S python3 -u\nprint(1)\n"), "python");
+        assert_eq!(classify(b"#!/bin/bash\necho hi\n"), "shell");
+        assert_eq!(classify(b"#!/opt/thing\n"), "script");
+        assert_eq!(classify("caf\u{e9}".as_bytes()), "text");
+        // a character cut off at the end of the sample is still text
+        assert_eq!(classify(&"\u{e9}".as_bytes()[..1]), "text");
+        assert_eq!(classify(b"\xff\xfe\x00\x01"), "binary");
+    }
+}
@@ -0,0 +1,475 @@
+use crate::hash::{hash_file, split_tagged, tagged, HashAlgo};
+use crate::{join_path, total_size, SizeStyle, TreeNode};
+use colored::Colorize;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+// canopy diff A B: what changed between two folders or two --json snapshots
+
+/// one side of the diff, `dir` is set when it came off the disk so files can be hashed
+pub struct Side {
+    pub tree: TreeNode,
+    pub dir: Option<PathBuf>,
+    pub label: String,
+}
+
+pub struct DiffOptions<'a> {
+    pub hash: bool,
+    pub all: bool,
+    pub sizes: &'a SizeStyle,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Unchanged,
+    Added,
+    Removed,
+    Modified,
+    Moved,
+}
+
+pub struct DiffNode {
+    name: String,
+    path: String,
+    is_dir: bool,
+    status: Status,
+    // file size, or everything below it for folders
+    before: Option<u64>,
+    after: Option<u64>,
+    moved_from: Option<String>,
+    children: Vec<DiffNode>,
+}
+
+// hashing reads whole files, so only do it for the ones that need it and only once
+struct Hashes<'a> {
+    side: &'a Side,
+    // hashing the disk is opt-in, hashes already in a snapshot are free to use
+    from_disk: bool,
+    algo: HashAlgo,
+    cache: RefCell<HashMap<String, Option<String>>>,
+}
+
+impl Hashes<'_> {
+    fn get(&self, path: &str, node: &TreeNode) -> Option<String> {
+        if let Some(hash) = node.hash.as_deref().filter(|h| split_tagged(h).is_some_and(|(a, _)| a == self.algo)) {
+            return Some(hash.to_string());
+        }
+        let dir = self.side.dir.as_ref().filter(|_| self.from_disk)?;
+        self.cache
+            .borrow_mut()
+            .entry(path.to_string())
+            .or_insert_with(|| hash_file(&dir.join(path), self.algo).ok().map(|hex| tagged(self.algo, &hex)))
+            .clone()
+    }
+}
+
+/// whatever a snapshot was hashed with, so the disk side gets hashed the same way
+fn snapshot_algo(node: &TreeNode) -> Option<HashAlgo> {
+    node.hash
+        .as_deref()
+        .and_then(split_tagged)
+        .map(|(algo, _)| algo)
+        .or_else(|| node.children.iter().find_map(snapshot_algo))
+}
+
+struct Ctx<'a> {
+    opts: &'a DiffOptions<'a>,
+    a: Hashes<'a>,
+    b: Hashes<'a>,
+    // new path -> old path, and the old paths that are accounted for by a move
+    moved_to: HashMap<String, String>,
+    moved_away: HashSet<String>,
+}
+
+pub fn diff_trees(a: &Side, b: &Side, opts: &DiffOptions) -> DiffNode {
+    let algo = snapshot_algo(&a.tree).or_else(|| snapshot_algo(&b.tree)).unwrap_or(HashAlgo::Sha256);
+    let mut ctx = Ctx {
+        opts,
+        a: Hashes { side: a, from_disk: opts.hash, algo, cache: RefCell::new(HashMap::new()) },
+        b: Hashes { side: b, from_disk: opts.hash, algo, cache: RefCell::new(HashMap::new()) },
+        moved_to: HashMap::new(),
+        moved_away: HashSet::new(),
+    };
+    find_moves(&mut ctx);
+    // both roots are folders by construction, so this never comes back empty
+    let mut root = merge(Some(&a.tree), Some(&b.tree), "", &ctx).unwrap();
+    root.name = format!("{} → {}", a.label, b.label);
+    root
+}
+
+fn files<'a>(node: &'a TreeNode, path: &str, out: &mut BTreeMap<String, &'a TreeNode>) {
+    for child in &node.children {
+        let child_path = join_path(path, &child.name);
+        if child.is_dir {
+            files(child, &child_path, out);
+        } else {
+            out.insert(child_path, child);
+        }
+    }
+}
+
+/// a file that vanished on one side and showed up on the other with the same content is a move.
+/// without --hash "same content" means same name and size, with it the hashes have to match
+fn find_moves(ctx: &mut Ctx) {
+    let (mut a_files, mut b_files) = (BTreeMap::new(), BTreeMap::new());
+    files(&ctx.a.side.tree, "", &mut a_files);
+    files(&ctx.b.side.tree, "", &mut b_files);
+    // empty files all look the same, matching them up would be noise
+    let removed: Vec<(&String, &TreeNode)> = a_files
+        .iter()
+        .filter(|(p, n)| !b_files.contains_key(*p) && n.size.unwrap_or(0) > 0)
+        .map(|(p, n)| (p, *n))
+        .collect();
+    let removed_sizes: HashSet<u64> = removed.iter().filter_map(|(_, n)| n.size).collect();
+
+    let mut candidates: HashMap<(u64, String), Vec<&String>> = HashMap::new();
+    for (path, node) in b_files.iter().rev() {
+        let size = node.size.unwrap_or(0);
+        if a_files.contains_key(path) || !removed_sizes.contains(&size) {
+            continue;
+        }
+        if let Some(key) = move_key(ctx, &ctx.b, path, node) {
+            candidates.entry((size, key)).or_default().push(path);
+        }
+    }
+    let mut moves = Vec::new();
+    for (path, node) in removed {
+        let Some(key) = move_key(ctx, &ctx.a, path, node) else { continue };
+        if let Some(new_path) = candidates.get_mut(&(node.size.unwrap_or(0), key)).and_then(|c| c.pop()) {
+            moves.push((new_path.clone(), path.clone()));
+        }
+    }
+    for (new_path, old_path) in moves {
+        ctx.moved_away.insert(old_path.clone());
+        ctx.moved_to.insert(new_path, old_path);
+    }
+}
+
+fn move_key(ctx: &Ctx, hashes: &Hashes, path: &str, node: &TreeNode) -> Option<String> {
+    if ctx.opts.hash {
+        hashes.get(path, node)
+    } else {
+        Some(node.name.clone())
+    }
+}
+
+fn file_changed(a: &TreeNode, b: &TreeNode, path: &str, ctx: &Ctx) -> bool {
+    if a.size != b.size {
+        return true;
+    }
+    if let (Some(x), Some(y)) = (ctx.a.get(path, a), ctx.b.get(path, b)) {
+        return x != y;
+    }
+    // same size and no hashes, a touched mtime is all we've got
+    matches!((a.mtime, b.mtime), (Some(x), Some(y)) if x != y)
+}
+
+fn merge(a: Option<&TreeNode>, b: Option<&TreeNode>, path: &str, ctx: &Ctx) -> Option<DiffNode> {
+    let node = b.or(a)?;
+    let is_dir = node.is_dir;
+    let status = match (a, b) {
+        (Some(_), None) if !is_dir && ctx.moved_away.contains(path) => return None,
+        (Some(_), None) => Status::Removed,
+        (None, Some(_)) if ctx.moved_to.contains_key(path) => Status::Moved,
+        (None, Some(_)) => Status::Added,
+        (Some(a), Some(b)) if !is_dir && file_changed(a, b, path, ctx) => Status::Modified,
+        _ => Status::Unchanged,
+    };
+
+    let mut children = Vec::new();
+    if is_dir {
+        // keyed on the type too, a file that became a folder is one removal plus one addition
+        type Pair<'n> = (Option<&'n TreeNode>, Option<&'n TreeNode>);
+        let mut pairs: BTreeMap<(&str, bool), Pair> = BTreeMap::new();
+        for child in a.into_iter().flat_map(|n| &n.children) {
+            pairs.entry((&child.name, child.is_dir)).or_default().0 = Some(child);
+        }
+        for child in b.into_iter().flat_map(|n| &n.children) {
+            pairs.entry((&child.name, child.is_dir)).or_default().1 = Some(child);
+        }
+        for ((name, _), (ca, cb)) in pairs {
+            children.extend(merge(ca, cb, &join_path(path, name), ctx));
+        }
+    }
+
+    let (before, after) = if is_dir {
+        (a.map(total_size), b.map(total_size))
+    } else {
+        (a.and_then(|n| n.size), b.and_then(|n| n.size))
+    };
+    Some(DiffNode {
+        name: node.name.clone(),
+        path: path.to_string(),
+        is_dir,
+        status,
+        before,
+        after,
+        moved_from: ctx.moved_to.get(path).filter(|_| status == Status::Moved).cloned(),
+        children,
+    })
+}
+
+#[derive(Serialize, Default)]
+struct Summary {
+    added: usize,
+    removed: usize,
+    modified: usize,
+    moved: usize,
+    size_before: u64,
+    size_after: u64,
+    size_delta: i64,
+}
+
+fn summarize(root: &DiffNode) -> Summary {
+    fn count(node: &DiffNode, s: &mut Summary) {
+        match node.status {
+            Status::Added => s.added += 1,
+            Status::Removed => s.removed += 1,
+            Status::Modified => s.modified += 1,
+            Status::Moved => s.moved += 1,
+            Status::Unchanged => {}
+        }
+        node.children.iter().for_each(|c| count(c, s));
+    }
+    let mut s = Summary {
+        size_before: root.before.unwrap_or(0),
+        size_after: root.after.unwrap_or(0),
+        ..Default::default()
+    };
+    s.size_delta = s.size_after as i64 - s.size_before as i64;
+    count(root, &mut s);
+    s
+}
+
+fn changed(node: &DiffNode) -> bool {
+    node.status != Status::Unchanged || node.children.iter().any(changed)
+}
+
+fn has_move(node: &DiffNode) -> bool {
+    node.status == Status::Moved || node.children.iter().any(has_move)
+}
+
+fn delta(node: &DiffNode) -> i64 {
+    node.after.unwrap_or(0) as i64 - node.before.unwrap_or(0) as i64
+}
+
+fn format_delta(d: i64, sizes: &SizeStyle) -> String {
+    let sign = if d < 0 { "-" } else { "+" };
+    format!("{}{}", sign, sizes.format(d.unsigned_abs()))
+}
+
+pub fn print_diff(root: &DiffNode, opts: &DiffOptions) {
+    let d = delta(root);
+    if d != 0 {
+        println!("{} ({})", root.name.bold(), color_delta(d, opts));
+    } else {
+        println!("{}", root.name.bold());
+    }
+    let shown: Vec<&DiffNode> = root.children.iter().filter(|c| opts.all || changed(c)).collect();
+    for (i, child) in shown.iter().enumerate() {
+        print_node(child, "", i == shown.len() - 1, opts);
+    }
+
+    let s = summarize(root);
+    println!();
+    if s.added + s.removed + s.modified + s.moved == 0 {
+        println!("no differences");
+    } else {
+        println!(
+            "{} added, {} removed, {} modified, {} moved",
+            s.added.to_string().green(),
+            s.removed.to_string().red(),
+            s.modified.to_string().yellow(),
+            s.moved.to_string().cyan()
+        );
+    }
+    println!(
+        "total {} → {} ({})",
+        opts.sizes.format(s.size_before),
+        opts.sizes.format(s.size_after),
+        color_delta(s.size_delta, opts)
+    );
+}
+
+fn color_delta(d: i64, opts: &DiffOptions) -> colored::ColoredString {
+    let text = format_delta(d, opts.sizes);
+    match d {
+        d if d > 0 => text.green(),
+        d if d < 0 => text.red(),
+        _ => text.dimmed(),
+    }
+}
+
+fn print_node(node: &DiffNode, prefix: &str, is_last: bool, opts: &DiffOptions) {
+    let connector = if is_last { "└── " } else { "├── " };
+    let name = if node.is_dir { format!("{}/", node.name) } else { node.name.clone() };
+    let size = |s: Option<u64>| opts.sizes.format(s.unwrap_or(0));
+    let line = match node.status {
+        Status::Added => format!("+ {} ({})", name, size(node.after)).green().to_string(),
+        Status::Removed => format!("- {} ({})", name, size(node.before)).red().to_string(),
+        Status::Modified if node.before == node.after => {
+            format!("~ {} ({}, contents or mtime changed)", name, size(node.after)).yellow().to_string()
+        }
+        Status::Modified => {
+            format!("~ {} ({} → {})", name, size(node.before), size(node.after)).yellow().to_string()
+        }
+        Status::Moved => format!(
+            "> {} ({}, moved from {})",
+            name,
+            size(node.after),
+            node.moved_from.as_deref().unwrap_or("?")
+        )
+        .cyan()
+        .to_string(),
+        // folders that stayed but have changes inside get their size delta
+        Status::Unchanged if node.is_dir && delta(node) != 0 => {
+            format!("  {} ({})", name.blue().bold(), color_delta(delta(node), opts))
+        }
+        Status::Unchanged if node.is_dir => format!("  {}", name.blue().bold()),
+        Status::Unchanged => format!("  {}", name).dimmed().to_string(),
+    };
+    println!("{}{}{}", prefix, connector, line);
+
+    // added and removed folders already say everything with their own line, except where things moved
+    let whole = matches!(node.status, Status::Added | Status::Removed);
+    let shown: Vec<&DiffNode> = node
+        .children
+        .iter()
+        .filter(|c| opts.all || if whole { has_move(c) } else { changed(c) })
+        .collect();
+    let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+    for (i, child) in shown.iter().enumerate() {
+        print_node(child, &new_prefix, i == shown.len() - 1, opts);
+    }
+}
+
+#[derive(Serialize)]
+struct Change<'a> {
+    path: &'a str,
+    status: Status,
+    is_dir: bool,
+    size_before: Option<u64>,
+    size_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    moved_from: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct DirDelta<'a> {
+    path: &'a str,
+    size_before: u64,
+    size_after: u64,
+    delta: i64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    before: &'a str,
+    after: &'a str,
+    summary: Summary,
+    changes: Vec<Change<'a>>,
+    directories: Vec<DirDelta<'a>>,
+}
+
+/// flat lists instead of the merged tree, easier to assert on from a ci script
+pub fn export_diff_json(root: &DiffNode, a: &Side, b: &Side) -> std::io::Result<()> {
+    fn collect<'a>(node: &'a DiffNode, changes: &mut Vec<Change<'a>>, dirs: &mut Vec<DirDelta<'a>>) {
+        let path = if node.path.is_empty() { "." } else { node.path.as_str() };
+        if node.status != Status::Unchanged {
+            changes.push(Change {
+                path,
+                status: node.status,
+                is_dir: node.is_dir,
+                size_before: node.before,
+                size_after: node.after,
+                moved_from: node.moved_from.as_deref(),
+            });
+        }
+        if node.is_dir && node.before.is_some() && node.after.is_some() && delta(node) != 0 {
+            dirs.push(DirDelta {
+                path,
+                size_before: node.before.unwrap_or(0),
+                size_after: node.after.unwrap_or(0),
+                delta: delta(node),
+            });
+        }
+        node.children.iter().for_each(|c| collect(c, changes, dirs));
+    }
+    let mut changes = Vec::new();
+    let mut directories = Vec::new();
+    collect(root, &mut changes, &mut directories);
+    let report = Report {
+        before: &a.label,
+        after: &b.label,
+        summary: summarize(root),
+        changes,
+        directories,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64, hash: Option<&str>) -> TreeNode {
+        TreeNode { name: name.to_string(), size: Some(size), hash: hash.map(str::to_string), ..Default::default() }
+    }
+
+    fn dir(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode { name: name.to_string(), is_dir: true, children, ..Default::default() }
+    }
+
+    fn changes(a: TreeNode, b: TreeNode, hash: bool) -> Vec<(String, Status, Option<String>)> {
+        fn walk(node: &DiffNode, out: &mut Vec<(String, Status, Option<String>)>) {
+            if node.status != Status::Unchanged {
+                out.push((node.path.clone(), node.status, node.moved_from.clone()));
+            }
+            node.children.iter().for_each(|c| walk(c, out));
+        }
+        let sizes = SizeStyle { si: false, exact: false, medium: 0, large: 0, big_file: 0 };
+        let opts = DiffOptions { hash, all: false, sizes: &sizes };
+        let side = |tree| Side { tree, dir: None, label: String::new() };
+        let mut out = Vec::new();
+        walk(&diff_trees(&side(a), &side(b), &opts), &mut out);
+        out
+    }
+
+    fn change(path: &str, status: Status, from: Option<&str>) -> (String, Status, Option<String>) {
+        (path.to_string(), status, from.map(str::to_string))
+    }
+
+    #[test]
+    fn same_name_and_size_is_a_move() {
+        let a = dir("a", vec![dir("docs", vec![file("notes.txt", 100, None)]), dir("src", vec![file("keep.rs", 5, None), file("old.rs", 0, None)])]);
+        let b = dir("b", vec![
+            dir("archive", vec![file("notes.txt", 100, None)]),
+            file("other.txt", 100, None),
+            dir("src", vec![file("keep.rs", 5, None), file("new.rs", 0, None)]),
+        ]);
+        assert_eq!(changes(a, b, false), [
+            change("archive", Status::Added, None),
+            change("archive/notes.txt", Status::Moved, Some("docs/notes.txt")),
+            change("docs", Status::Removed, None),
+            // same size under another name isn't a move without --hash, and empty files never are
+            change("other.txt", Status::Added, None),
+            change("src/new.rs", Status::Added, None),
+            change("src/old.rs", Status::Removed, None),
+        ]);
+    }
+
+    #[test]
+    fn with_hashes_the_content_decides() {
+        let a = dir("a", vec![file("a.bin", 10, Some("sha256:aaaa")), file("b.bin", 10, Some("sha256:bbbb"))]);
+        let b = dir("b", vec![file("other.bin", 10, Some("sha256:cccc")), file("renamed.bin", 10, Some("sha256:aaaa"))]);
+        assert_eq!(changes(a, b, true), [
+            change("b.bin", Status::Removed, None),
+            change("other.bin", Status::Added, None),
+            change("renamed.bin", Status::Moved, Some("a.bin")),
+        ]);
+    }
+}
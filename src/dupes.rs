@@ -0,0 +1,377 @@
+use crate::hash::{hash_file, tagged, HashAlgo};
+use crate::{join_path, SizeStyle, TreeNode};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color as TuiColor, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Terminal,
+};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// canopy dupes: same size first, then a quick hash of both ends, then the full hash.
+// each stage only looks at what survived the one before, so most files are never read at all
+
+pub struct DupeOptions {
+    pub min_size: u64,
+    pub merge_hardlinks: bool,
+    pub algo: HashAlgo,
+}
+
+#[derive(Serialize)]
+pub struct Group {
+    pub size: u64,
+    pub hash: String,
+    /// paths below the scan root, sorted
+    pub paths: Vec<String>,
+    pub wasted: u64,
+}
+
+const BLOCK: u64 = 4096;
+
+pub fn find_dupes(root: &Path, tree: &TreeNode, opts: &DupeOptions) -> Vec<Group> {
+    let mut files = Vec::new();
+    collect(tree, "", opts.min_size.max(1), &mut files);
+    if opts.merge_hardlinks {
+        files = merge_hardlinks(root, files);
+    }
+
+    // stage 1: sizes
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    for (path, size) in files {
+        by_size.entry(size).or_default().push(path);
+    }
+    let candidates: Vec<(u64, Vec<String>)> = by_size.into_iter().filter(|(_, p)| p.len() > 1).collect();
+
+    // stage 2: first and last block. small files are read whole here, so their hash is already final
+    let partial = split_by(candidates, |size, path| {
+        if size <= BLOCK * 2 {
+            hash_file(&root.join(path), opts.algo).ok().map(|h| format!("full:{}", h))
+        } else {
+            hash_ends(&root.join(path)).ok().map(|h| format!("ends:{:016x}", h))
+        }
+    });
+
+    // stage 3: everything, for the big files that still look alike
+    let (done, big): (Vec<_>, Vec<_>) = partial.into_iter().partition(|(_, key, _)| key.starts_with("full:"));
+    let full = split_by(
+        big.into_iter().map(|(size, _, paths)| (size, paths)).collect(),
+        |_, path| hash_file(&root.join(path), opts.algo).ok(),
+    );
+
+    let mut groups: Vec<Group> = done
+        .into_iter()
+        .map(|(size, key, paths)| (size, key.trim_start_matches("full:").to_string(), paths))
+        .chain(full)
+        .map(|(size, hex, mut paths)| {
+            paths.sort();
+            Group { size, hash: tagged(opts.algo, &hex), wasted: size * (paths.len() as u64 - 1), paths }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.wasted.cmp(&a.wasted).then_with(|| a.paths.cmp(&b.paths)));
+    groups
+}
+
+fn collect(node: &TreeNode, path: &str, min_size: u64, out: &mut Vec<(String, u64)>) {
+    for child in &node.children {
+        let child_path = join_path(path, &child.name);
+        if child.is_dir {
+            collect(child, &child_path, min_size, out);
+        } else if child.special.is_none() && child.size.unwrap_or(0) >= min_size {
+            // symlinks point at a file that's counted already, fifos and devices aren't files
+            out.push((child_path, child.size.unwrap_or(0)));
+        }
+    }
+}
+
+/// hash every path in every group, regroup by (size, hash) and keep what still has company
+fn split_by<F>(groups: Vec<(u64, Vec<String>)>, key: F) -> Vec<(u64, String, Vec<String>)>
+where
+    F: Fn(u64, &str) -> Option<String> + Sync,
+{
+    let keyed: Vec<(u64, String, String)> = groups
+        .into_par_iter()
+        .flat_map_iter(|(size, paths)| paths.into_iter().map(move |p| (size, p)))
+        .filter_map(|(size, path)| key(size, &path).map(|k| (size, k, path)))
+        .collect();
+    let mut regrouped: BTreeMap<(u64, String), Vec<String>> = BTreeMap::new();
+    for (size, k, path) in keyed {
+        regrouped.entry((size, k)).or_default().push(path);
+    }
+    regrouped
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, k), paths)| (size, k, paths))
+        .collect()
+}
+
+fn hash_ends(path: &Path) -> io::Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; BLOCK as usize];
+    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+    file.read_exact(&mut buf)?;
+    hasher.update(&buf);
+    file.seek(SeekFrom::End(-(BLOCK as i64)))?;
+    file.read_exact(&mut buf)?;
+    hasher.update(&buf);
+    Ok(hasher.digest())
+}
+
+/// hard links are the same bytes on disk, keep one name per inode so they don't count as waste
+#[cfg(unix)]
+fn merge_hardlinks(root: &Path, files: Vec<(String, u64)>) -> Vec<(String, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let mut seen = HashSet::new();
+    files
+        .into_iter()
+        .filter(|(path, _)| match std::fs::symlink_metadata(root.join(path)) {
+            Ok(md) => md.nlink() < 2 || seen.insert((md.dev(), md.ino())),
+            Err(_) => true,
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn merge_hardlinks(_root: &Path, files: Vec<(String, u64)>) -> Vec<(String, u64)> {
+    // std has no stable file index on windows, hard links are rare enough there to let it be
+    files
+}
+
+/// the scanned tree cut down to the duplicates, each one noted with its group
+pub fn dupes_tree(tree: &TreeNode, groups: &[Group]) -> TreeNode {
+    let mut notes = HashMap::new();
+    for (i, g) in groups.iter().enumerate() {
+        for p in &g.paths {
+            notes.insert(p.as_str(), format!("dupe #{}, {} copies", i + 1, g.paths.len()));
+        }
+    }
+    prune(tree, "", &notes).unwrap_or_else(|| TreeNode { name: tree.name.clone(), is_dir: true, ..Default::default() })
+}
+
+fn prune(node: &TreeNode, path: &str, notes: &HashMap<&str, String>) -> Option<TreeNode> {
+    if !node.is_dir {
+        let note = notes.get(path)?;
+        return Some(TreeNode { name: node.name.clone(), size: node.size, note: Some(note.clone()), ..Default::default() });
+    }
+    let children: Vec<TreeNode> = node
+        .children
+        .iter()
+        .filter_map(|c| {
+            let child_path = join_path(path, &c.name);
+            prune(c, &child_path, notes)
+        })
+        .collect();
+    if children.is_empty() && !path.is_empty() {
+        return None;
+    }
+    Some(TreeNode { name: node.name.clone(), is_dir: true, children, ..Default::default() })
+}
+
+pub fn print_groups(groups: &[Group], sizes: &SizeStyle) {
+    use colored::Colorize;
+    for (i, g) in groups.iter().enumerate() {
+        println!(
+            "{} {} x {}, {} wasted",
+            format!("#{}", i + 1).bold(),
+            g.paths.len(),
+            sizes.format(g.size),
+            sizes.format(g.wasted).color(sizes.color(g.wasted))
+        );
+        for p in &g.paths {
+            println!("    {}", p);
+        }
+    }
+    let wasted: u64 = groups.iter().map(|g| g.wasted).sum();
+    let files: usize = groups.iter().map(|g| g.paths.len()).sum();
+    println!();
+    if groups.is_empty() {
+        println!("no duplicates");
+    } else {
+        println!("{} groups, {} files, {} wasted", groups.len(), files, sizes.format(wasted).bold());
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    root: &'a str,
+    groups: &'a [Group],
+    duplicate_files: usize,
+    wasted: u64,
+}
+
+pub fn export_dupes_json(groups: &[Group], root: &str) -> io::Result<()> {
+    let report = Report {
+        root,
+        groups,
+        duplicate_files: groups.iter().map(|g| g.paths.len()).sum(),
+        wasted: groups.iter().map(|g| g.wasted).sum(),
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+// one row in the tui list, either a group header or one of its files
+enum Row {
+    Group(usize),
+    File(usize, usize),
+}
+
+/// browse the groups, mark copies with space and send them to the trash in one go
+pub fn run_dupes_tui(root: &Path, groups: &mut Vec<Group>, sizes: &SizeStyle) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    crossterm::execute!(stdout, crossterm::terminal::EnterAlternateScreen)?;
+    crossterm::terminal::enable_raw_mode()?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut marked: HashSet<(usize, usize)> = HashSet::new();
+    let mut selected = 0;
+    let mut status = "space: mark  a: mark all but the first  c: clear  d: trash marked  q: quit".to_string();
+    let mut confirm = false;
+
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| -> io::Result<()> {
+        loop {
+            let rows: Vec<Row> = groups
+                .iter()
+                .enumerate()
+                .flat_map(|(g, group)| std::iter::once(Row::Group(g)).chain((0..group.paths.len()).map(move |f| Row::File(g, f))))
+                .collect();
+            selected = selected.min(rows.len().saturating_sub(1));
+            let marked_bytes: u64 = marked.iter().map(|(g, _)| groups[*g].size).sum();
+
+            terminal.draw(|f| {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(3), Constraint::Length(3)])
+                    .split(f.size());
+                let items: Vec<ListItem> = rows
+                    .iter()
+                    .map(|row| match *row {
+                        Row::Group(g) => ListItem::new(Line::from(Span::styled(
+                            format!(
+                                "#{}  {} x {}, {} wasted",
+                                g + 1,
+                                groups[g].paths.len(),
+                                sizes.format(groups[g].size),
+                                sizes.format(groups[g].wasted)
+                            ),
+                            Style::default().fg(TuiColor::Blue).add_modifier(Modifier::BOLD),
+                        ))),
+                        Row::File(g, i) => {
+                            let is_marked = marked.contains(&(g, i));
+                            let style = if is_marked { Style::default().fg(TuiColor::Red) } else { Style::default() };
+                            ListItem::new(Line::from(vec![
+                                Span::styled(if is_marked { "  [x] " } else { "  [ ] " }, style),
+                                Span::styled(groups[g].paths[i].clone(), style),
+                            ]))
+                        }
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(format!(
+                        "duplicates in {} ({} marked, {})",
+                        root.display(),
+                        marked.len(),
+                        sizes.format(marked_bytes)
+                    )))
+                    .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+                    .highlight_symbol(">> ");
+                let mut state = ListState::default();
+                state.select(Some(selected));
+                f.render_stateful_widget(list, chunks[0], &mut state);
+                f.render_widget(Paragraph::new(status.as_str()).block(Block::default().borders(Borders::ALL)), chunks[1]);
+            })?;
+
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if confirm {
+                confirm = false;
+                if key.code == KeyCode::Char('y') {
+                    status = trash_marked(root, groups, &mut marked);
+                } else {
+                    status = "nothing trashed".to_string();
+                }
+                continue;
+            }
+            match key.code {
+                KeyCode::Up => selected = selected.saturating_sub(1),
+                KeyCode::Down if selected + 1 < rows.len() => selected += 1,
+                KeyCode::Char(' ') => {
+                    if let Some(Row::File(g, i)) = rows.get(selected) {
+                        if !marked.remove(&(*g, *i)) {
+                            marked.insert((*g, *i));
+                        }
+                    }
+                }
+                // keep the first path of every group, which is the one that sorts first
+                KeyCode::Char('a') => {
+                    for (g, group) in groups.iter().enumerate() {
+                        marked.extend((1..group.paths.len()).map(|i| (g, i)));
+                    }
+                }
+                KeyCode::Char('c') => marked.clear(),
+                KeyCode::Char('d') if !marked.is_empty() => {
+                    status = format!("move {} files ({}) to the trash? y/n", marked.len(), sizes.format(marked_bytes));
+                    confirm = true;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }));
+
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(terminal.backend_mut(), crossterm::terminal::LeaveAlternateScreen)?;
+    match res {
+        Ok(inner) => inner,
+        Err(_) => Err(io::Error::other("TUI Panicked!!!")),
+    }
+}
+
+/// trash what's marked, but never every copy in a group, one always stays
+fn trash_marked(root: &Path, groups: &mut Vec<Group>, marked: &mut HashSet<(usize, usize)>) -> String {
+    let mut trashed = 0;
+    let mut kept_groups = 0;
+    let mut failed = Vec::new();
+    for (g, group) in groups.iter_mut().enumerate() {
+        let mine: Vec<usize> = (0..group.paths.len()).filter(|i| marked.contains(&(g, *i))).collect();
+        if mine.is_empty() {
+            continue;
+        }
+        if mine.len() == group.paths.len() {
+            kept_groups += 1;
+            continue;
+        }
+        let mut gone = HashSet::new();
+        for i in mine {
+            match trash::delete(root.join(&group.paths[i])) {
+                Ok(()) => {
+                    trashed += 1;
+                    gone.insert(group.paths[i].clone());
+                }
+                Err(e) => failed.push(format!("{}: {}", group.paths[i], e)),
+            }
+        }
+        group.paths.retain(|p| !gone.contains(p));
+        group.wasted = group.size * (group.paths.len() as u64).saturating_sub(1);
+    }
+    marked.clear();
+    groups.retain(|g| g.paths.len() > 1);
+    let mut status = format!("trashed {} files", trashed);
+    if kept_groups > 0 {
+        status.push_str(&format!(", skipped {} groups with every copy marked", kept_groups));
+    }
+    if let Some(first) = failed.first() {
+        status.push_str(&format!(", {} failed ({})", failed.len(), first));
+    }
+    status
+}
@@ -0,0 +1,271 @@
+use crate::git::GitState;
+use crate::loc::Loc;
+use crate::meta::{format_mode, format_time_iso};
+use crate::{escape_xml, ScanError, TreeNode};
+use serde_json::{Map, Value};
+use serde::Serialize;
+use std::io::{self, Write};
+
+// the other serde-ish exports, all of them write the same TreeNode that --json does
+
+pub fn export_yaml(tree: &TreeNode) -> io::Result<()> {
+    print!("{}", to_yaml(tree)?);
+    Ok(())
+}
+
+fn to_yaml(tree: &TreeNode) -> io::Result<String> {
+    // through serde's json value so every TreeNode field comes along, in declaration order
+    let value = serde_json::to_value(tree)?;
+    let mut yaml = String::new();
+    match &value {
+        Value::Object(map) => yaml_map(&mut yaml, map, 0, false),
+        other => yaml.push_str(&format!("{}\n", yaml_scalar(other))),
+    }
+    Ok(yaml)
+}
+
+// block style, laid out like serde_yaml did: sequences sit at their key's indent, `- ` opens each item
+fn yaml_map(out: &mut String, map: &Map<String, Value>, indent: usize, first_inline: bool) {
+    for (i, (key, value)) in map.iter().enumerate() {
+        if i > 0 || !first_inline {
+            out.push_str(&" ".repeat(indent));
+        }
+        out.push_str(&yaml_string(key));
+        match value {
+            Value::Object(inner) if !inner.is_empty() => {
+                out.push_str(":\n");
+                yaml_map(out, inner, indent + 2, false);
+            }
+            Value::Array(items) if !items.is_empty() => {
+                out.push_str(":\n");
+                yaml_seq(out, items, indent);
+            }
+            _ => out.push_str(&format!(": {}\n", yaml_scalar(value))),
+        }
+    }
+}
+
+fn yaml_seq(out: &mut String, items: &[Value], indent: usize) {
+    for item in items {
+        out.push_str(&" ".repeat(indent));
+        out.push_str("- ");
+        match item {
+            Value::Object(map) if !map.is_empty() => yaml_map(out, map, indent + 2, true),
+            _ => out.push_str(&format!("{}\n", yaml_scalar(item))),
+        }
+    }
+}
+
+// scalars, empty containers and anything nested deeper go inline. json is valid yaml flow style
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::String(s) => yaml_string(s),
+        other => other.to_string(),
+    }
+}
+
+/// plain when every yaml reader, 1.1 ones included, would read it back as the same string, double quoted otherwise
+fn yaml_string(s: &str) -> String {
+    let plain_chars = s.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '/' | '+' | ' ' | '(' | ')'));
+    // numbers, dates (`2024-01-01`), `1_000`, `+1` and `.inf` all start with a digit, a sign or a dot
+    let starts_ok = s.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '/');
+    // yaml 1.1 booleans and nulls, `inf` and `nan` are floats to rust so they'd look odd to some readers too
+    let reserved = matches!(
+        s.to_lowercase().as_str(),
+        "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "y" | "n" | "inf" | "infinity" | "nan"
+    );
+    if plain_chars && starts_ok && !reserved && !s.ends_with(' ') {
+        s.to_string()
+    } else {
+        serde_json::to_string(s).unwrap_or_default()
+    }
+}
+
+pub fn export_toml(tree: &TreeNode) -> io::Result<()> {
+    // toml has no null, so entries without a size just leave the key out
+    let text = toml::to_string_pretty(tree).map_err(io::Error::other)?;
+    print!("{}", text);
+    Ok(())
+}
+
+/// one json object per line, every entry with its full path, for log pipelines
+#[derive(Serialize)]
+pub struct FlatEntry<'a> {
+    pub path: &'a str,
+    pub name: &'a str,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loc: Option<Loc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detected: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a ScanError>,
+}
+
+impl<'a> FlatEntry<'a> {
+    pub fn new(node: &'a TreeNode, path: &'a str) -> FlatEntry<'a> {
+        FlatEntry {
+            path,
+            name: &node.name,
+            is_dir: node.is_dir,
+            size: node.size,
+            mode: node.mode,
+            owner: node.owner.as_deref(),
+            group: node.group.as_deref(),
+            mtime: node.mtime,
+            hash: node.hash.as_deref(),
+            note: node.note.as_deref(),
+            loc: node.loc,
+            detected: node.detected.as_deref(),
+            git: node.git,
+            error: node.error.as_ref(),
+        }
+    }
+}
+
+pub fn export_ndjson(tree: &TreeNode, root: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    write_ndjson(&mut out, tree, root)?;
+    out.flush()
+}
+
+fn write_ndjson<W: Write>(out: &mut W, node: &TreeNode, path: &str) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, &FlatEntry::new(node, path))?;
+    out.write_all(b"\n")?;
+    for child in &node.children {
+        let child_path = format!("{}/{}", path.trim_end_matches(['/', '\\']), child.name);
+        write_ndjson(out, child, &child_path)?;
+    }
+    Ok(())
+}
+
+/// same shape as GNU `tree -X`, so anything that reads that reads this
+pub fn export_xml(tree: &TreeNode, root: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, "<tree>")?;
+    let (mut dirs, mut files) = (0, 0);
+    write_xml(&mut out, tree, Some(root), 1, &mut dirs, &mut files)?;
+    writeln!(out, "  <report>")?;
+    writeln!(out, "    <directories>{}</directories>", dirs)?;
+    writeln!(out, "    <files>{}</files>", files)?;
+    writeln!(out, "  </report>")?;
+    writeln!(out, "</tree>")?;
+    out.flush()
+}
+
+fn write_xml<W: Write>(out: &mut W, node: &TreeNode, name: Option<&str>, depth: usize, dirs: &mut usize, files: &mut usize) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    let tag = if node.is_dir { "directory" } else { "file" };
+    let mut attrs = format!(r#"name="{}""#, escape_xml(name.unwrap_or(&node.name)));
+    if let Some(mode) = node.mode {
+        attrs.push_str(&format!(r#" mode="{:04o}" prot="{}""#, mode & 0o7777, format_mode(mode)));
+    }
+    if let Some(owner) = &node.owner {
+        attrs.push_str(&format!(r#" user="{}""#, escape_xml(owner)));
+    }
+    if let Some(group) = &node.group {
+        attrs.push_str(&format!(r#" group="{}""#, escape_xml(group)));
+    }
+    if let Some(size) = node.size {
+        attrs.push_str(&format!(r#" size="{}""#, size));
+    }
+    if let Some(mtime) = node.mtime {
+        attrs.push_str(&format!(r#" time="{}""#, format_time_iso(mtime)));
+    }
+    if let Some(hash) = &node.hash {
+        attrs.push_str(&format!(r#" hash="{}""#, escape_xml(hash)));
+    }
+    if let Some(note) = &node.note {
+        attrs.push_str(&format!(r#" note="{}""#, escape_xml(note)));
+    }
+    if let Some(detected) = &node.detected {
+        attrs.push_str(&format!(r#" detected="{}""#, escape_xml(detected)));
+    }
+    if let Some(state) = node.git {
+        attrs.push_str(&format!(r#" git="{}""#, state.name()));
+    }
+    if let Some(error) = &node.error {
+        attrs.push_str(&format!(r#" error="{}""#, escape_xml(&error.message)));
+    }
+    if let Some(loc) = &node.loc {
+        attrs.push_str(&format!(r#" lines="{}" code="{}" comments="{}" blank="{}""#, loc.lines, loc.code, loc.comments, loc.blank));
+    }
+    // the root folder doesn't count towards the report, tree doesn't count it either
+    if depth > 1 {
+        if node.is_dir {
+            *dirs += 1;
+        } else {
+            *files += 1;
+        }
+    }
+    if node.children.is_empty() {
+        return writeln!(out, "{}<{} {}></{}>", indent, tag, attrs, tag);
+    }
+    writeln!(out, "{}<{} {}>", indent, tag, attrs)?;
+    for child in &node.children {
+        write_xml(out, child, None, depth + 1, dirs, files)?;
+    }
+    writeln!(out, "{}</{}>", indent, tag)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_strings_that_look_like_something_else_are_quoted() {
+        for s in ["2024-01-01", "1_000", "1.5", "0x10", "+1", "-x", ".inf", ".hidden", "true", "Yes", "off", "y", "null", "~", "", "a: b", "#h", "12:30", "sp end ", "q\"t"] {
+            assert!(yaml_string(s).starts_with('"'), "{} should be quoted, got {}", s, yaml_string(s));
+        }
+        for s in ["main.rs", "src", "Cargo.toml", "my file (1).txt", "_build", "/usr/bin", "é ñ", "permission_denied"] {
+            assert_eq!(yaml_string(s), s);
+        }
+    }
+
+    #[test]
+    fn yaml_round_trips() {
+        let names = ["2024-01-01", "1_000", "true", "a: b", "-x", "#h", "sp end ", "é ñ", "q\"t", "back\\slash", "tab\there", "main.rs"];
+        let tree = TreeNode {
+            name: String::new(),
+            is_dir: true,
+            note: Some("line one\nline two".to_string()),
+            children: names.iter().enumerate().map(|(i, n)| TreeNode { name: n.to_string(), size: Some(i as u64), ..Default::default() }).collect(),
+            ..Default::default()
+        };
+        let yaml = to_yaml(&tree).unwrap();
+        let back: TreeNode = serde_norway::from_str(&yaml).unwrap();
+        assert_eq!(serde_json::to_string(&back).unwrap(), serde_json::to_string(&tree).unwrap());
+    }
+
+    #[test]
+    fn yaml_layout_matches_serde_yaml() {
+        let tree = TreeNode {
+            name: "proj".to_string(),
+            is_dir: true,
+            children: vec![TreeNode { name: "a.rs".to_string(), size: Some(3), ..Default::default() }],
+            ..Default::default()
+        };
+        let expected = "name: proj\nis_dir: true\nsize: null\nchildren:\n- name: a.rs\n  is_dir: false\n  size: 3\n  children: []\n";
+        assert_eq!(to_yaml(&tree).unwrap(), expected);
+    }
+}
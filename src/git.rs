@@ -0,0 +1,242 @@
+use crate::{join_path, sort_tree, Special, TreeNode};
+use colored::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+// --git: each entry's state in the repository it lives in, straight from `git status`.
+// that reads the index and the working tree, nothing goes over the network
+
+/// worst last, so a folder's state is the max of what's inside it
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GitState {
+    Ignored,
+    Clean,
+    Untracked,
+    Staged,
+    Modified,
+    Conflicted,
+}
+
+impl GitState {
+    /// same word the json export uses
+    pub fn name(self) -> &'static str {
+        match self {
+            GitState::Ignored => "ignored",
+            GitState::Clean => "clean",
+            GitState::Untracked => "untracked",
+            GitState::Staged => "staged",
+            GitState::Modified => "modified",
+            GitState::Conflicted => "conflicted",
+        }
+    }
+
+    /// one character, like the columns `git status -s` prints
+    pub fn marker(self) -> &'static str {
+        match self {
+            GitState::Ignored => "!",
+            GitState::Clean => "-",
+            GitState::Untracked => "?",
+            GitState::Staged => "S",
+            GitState::Modified => "M",
+            GitState::Conflicted => "U",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            GitState::Ignored | GitState::Clean => Color::BrightBlack,
+            GitState::Untracked => Color::Cyan,
+            GitState::Staged => Color::Green,
+            GitState::Modified => Color::Yellow,
+            GitState::Conflicted => Color::Red,
+        }
+    }
+
+    // the XY pair of porcelain v1: X is the index, Y the working tree
+    fn from_xy(x: u8, y: u8) -> GitState {
+        match (x, y) {
+            (b'?', b'?') => GitState::Untracked,
+            (b'!', b'!') => GitState::Ignored,
+            (b'U', _) | (_, b'U') | (b'A', b'A') | (b'D', b'D') => GitState::Conflicted,
+            (_, b' ') => GitState::Staged,
+            _ => GitState::Modified,
+        }
+    }
+}
+
+pub struct GitStatus {
+    /// where the scanned folder sits below the repository root, `sub/dir/` or empty
+    prefix: String,
+    files: HashMap<String, GitState>,
+    /// untracked and ignored folders come back whole, as `dir/`
+    dirs: Vec<(String, GitState)>,
+}
+
+/// run `git status` for the repository `root` is in
+pub fn load(root: &Path) -> Result<GitStatus, String> {
+    let prefix = git(root, &["rev-parse", "--show-prefix"])?;
+    let prefix = String::from_utf8_lossy(&prefix).trim_end_matches(['\n', '\r']).to_string();
+    // paths in porcelain output are relative to the repository root, whatever folder we ask from
+    let out = git(root, &["status", "--porcelain=v1", "-z", "--ignored", "--", "."])?;
+    let mut status = GitStatus { prefix, files: HashMap::new(), dirs: Vec::new() };
+    let mut records = out.split(|&b| b == 0).filter(|r| !r.is_empty());
+    while let Some(record) = records.next() {
+        if record.len() < 4 {
+            continue;
+        }
+        let state = GitState::from_xy(record[0], record[1]);
+        let path = String::from_utf8_lossy(&record[3..]).to_string();
+        // renames and copies are followed by the path they came from, which is gone now
+        if matches!(record[0], b'R' | b'C') {
+            records.next();
+        }
+        if path.ends_with('/') {
+            status.dirs.push((path, state));
+        } else {
+            status.files.insert(path, state);
+        }
+    }
+    Ok(status)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("couldn't run git: {}", e))?;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    Ok(out.stdout)
+}
+
+impl GitStatus {
+    /// state of the entry at `rel` below the scanned folder. folders get the worst state inside them,
+    /// so a folder with one edited file shows as modified
+    pub fn state(&self, rel: &str, is_dir: bool) -> GitState {
+        let full = format!("{}{}", self.prefix, rel);
+        let as_dir = if full.is_empty() || full.ends_with('/') { full.clone() } else { format!("{}/", full) };
+        if let Some((_, state)) = self.dirs.iter().find(|(d, _)| as_dir.starts_with(d.as_str())) {
+            return *state;
+        }
+        if !is_dir {
+            return self.files.get(&full).copied().unwrap_or(GitState::Clean);
+        }
+        let inside = self.files.iter().filter(|(f, _)| f.starts_with(&as_dir)).map(|(_, s)| *s);
+        let inside_dirs = self.dirs.iter().filter(|(d, _)| d.starts_with(&as_dir)).map(|(_, s)| *s);
+        // ignored stuff inside a tracked folder doesn't make the folder ignored
+        inside.chain(inside_dirs).max().unwrap_or(GitState::Clean).max(GitState::Clean)
+    }
+
+    pub fn fill(&self, tree: &mut TreeNode) {
+        self.fill_node(tree, "");
+    }
+
+    fn fill_node(&self, node: &mut TreeNode, rel: &str) {
+        node.git = Some(self.state(rel, node.is_dir));
+        for child in node.children.iter_mut() {
+            // git's own folder isn't part of the repository's contents
+            if child.is_dir && child.name == ".git" {
+                continue;
+            }
+            let child_rel = join_path(rel, &child.name);
+            self.fill_node(child, &child_rel);
+        }
+    }
+}
+
+/// --git-rev: the tree `root` had at `rev`, read from the object database. the working copy isn't touched
+pub fn rev_tree(root: &Path, rev: &str) -> Result<TreeNode, String> {
+    let prefix = git(root, &["rev-parse", "--show-prefix"])?;
+    let prefix = String::from_utf8_lossy(&prefix).trim_end_matches(['\n', '\r', '/']).to_string();
+    // `rev:sub/dir` is the subtree itself, so the paths come back relative to it.
+    // without --full-tree ls-tree would also keep only what's under the cwd prefix inside it, which is nothing
+    let treeish = format!("{}:{}", rev, prefix);
+    let out = git(root, &["ls-tree", "--full-tree", "-r", "-t", "-l", "-z", &treeish])?;
+    let name = if prefix.is_empty() { rev.to_string() } else { treeish };
+    let mut tree = TreeNode { name, is_dir: true, ..Default::default() };
+    for record in out.split(|&b| b == 0).filter(|r| !r.is_empty()) {
+        let record = String::from_utf8_lossy(record);
+        // `<mode> <type> <object> <size>\t<path>`, size is `-` for trees and submodules
+        let Some((info, path)) = record.split_once('\t') else { continue };
+        let mut fields = info.split_whitespace();
+        let (Some(mode), Some(kind), Some(_), Some(size)) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let mode = u32::from_str_radix(mode, 8).ok();
+        let is_dir = kind != "blob";
+        let node = TreeNode {
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+            is_dir,
+            size: if is_dir { None } else { size.parse().ok() },
+            // git only keeps the file type and the executable bit, that's still a mode for files.
+            // a tree's 040000 has no permissions at all, better none than d---------
+            mode: if is_dir { None } else { mode },
+            special: if mode == Some(0o120000) { Some(Special::Symlink) } else { None },
+            ..Default::default()
+        };
+        insert(&mut tree, path, node);
+    }
+    sort_tree(&mut tree);
+    Ok(tree)
+}
+
+// ls-tree lists a folder before what's in it, so the parents are always there already
+fn insert(tree: &mut TreeNode, path: &str, node: TreeNode) {
+    let mut parent = tree;
+    let mut parts: Vec<&str> = path.split('/').collect();
+    parts.pop();
+    for part in parts {
+        let Some(i) = parent.children.iter().position(|c| c.is_dir && c.name == part) else { return };
+        parent = &mut parent.children[i];
+    }
+    parent.children.push(node);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn porcelain_xy_pairs() {
+        let cases = [
+            (b"??", GitState::Untracked),
+            (b"!!", GitState::Ignored),
+            (b"UU", GitState::Conflicted),
+            (b"AU", GitState::Conflicted),
+            (b"DU", GitState::Conflicted),
+            (b"AA", GitState::Conflicted),
+            (b"DD", GitState::Conflicted),
+            (b"M ", GitState::Staged),
+            (b"A ", GitState::Staged),
+            (b"R ", GitState::Staged),
+            (b" M", GitState::Modified),
+            (b"MM", GitState::Modified),
+            (b"AM", GitState::Modified),
+            (b" D", GitState::Modified),
+        ];
+        for (xy, state) in cases {
+            assert_eq!(GitState::from_xy(xy[0], xy[1]), state, "{}", String::from_utf8_lossy(xy));
+        }
+    }
+
+    #[test]
+    fn folders_take_the_worst_state_inside() {
+        let status = GitStatus {
+            prefix: "sub/".to_string(),
+            files: HashMap::from([("sub/src/a.rs".to_string(), GitState::Staged), ("sub/src/b.rs".to_string(), GitState::Modified)]),
+            dirs: vec![("sub/new/".to_string(), GitState::Untracked), ("sub/src/build/".to_string(), GitState::Ignored)],
+        };
+        assert_eq!(status.state("src", true), GitState::Modified);
+        assert_eq!(status.state("src/a.rs", false), GitState::Staged);
+        assert_eq!(status.state("src/c.rs", false), GitState::Clean);
+        assert_eq!(status.state("src/build", true), GitState::Ignored);
+        assert_eq!(status.state("new/deep/x.rs", false), GitState::Untracked);
+        assert_eq!(status.state("", true), GitState::Modified);
+    }
+}
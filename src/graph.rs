@@ -0,0 +1,150 @@
+use crate::{total_size, SizeStyle, TreeNode};
+
+// --export dot / --export mermaid, the tree as a graph for architecture docs
+
+pub struct GraphOptions<'a> {
+    pub max_depth: Option<usize>,
+    pub sizes: Option<&'a SizeStyle>,
+    pub cluster: bool,
+}
+
+fn count_entries(node: &TreeNode) -> usize {
+    node.children.len() + node.children.iter().map(count_entries).sum::<usize>()
+}
+
+fn label(node: &TreeNode, root_name: Option<&str>, depth: usize, opts: &GraphOptions) -> String {
+    let mut label = root_name.unwrap_or(&node.name).to_string();
+    if node.is_dir {
+        label.push('/');
+    }
+    if let Some(sizes) = opts.sizes {
+        let size = if node.is_dir { total_size(node) } else { node.size.unwrap_or(0) };
+        label.push_str(&format!(" ({})", sizes.format(size)));
+    }
+    // at the depth cap, say how much got hidden instead of just stopping
+    if node.is_dir && !node.children.is_empty() && opts.max_depth.is_some_and(|d| depth >= d) {
+        label.push_str(&format!(" [+{} more]", count_entries(node)));
+    }
+    label
+}
+
+fn expand(depth: usize, opts: &GraphOptions) -> bool {
+    opts.max_depth.is_none_or(|d| depth < d)
+}
+
+pub fn export_dot(tree: &TreeNode, root_name: &str, opts: &GraphOptions) -> std::io::Result<()> {
+    let mut out = String::new();
+    out.push_str("digraph canopy {\n");
+    out.push_str("    rankdir=LR;\n");
+    out.push_str("    node [fontname=\"Helvetica\", fontsize=10];\n");
+    out.push_str("    edge [color=\"#999999\", arrowsize=0.6];\n");
+    let mut next_id = 0;
+    let root = dot_node(&mut out, tree, Some(root_name), 0, opts, &mut next_id, "    ");
+    if expand(0, opts) {
+        for child in &tree.children {
+            // each top level folder gets its own box when clustering
+            if opts.cluster && child.is_dir {
+                let cluster_id = next_id;
+                out.push_str(&format!("    subgraph cluster_{} {{\n", cluster_id));
+                out.push_str(&format!("        label=\"{}/\";\n", dot_escape(&child.name)));
+                out.push_str("        style=\"rounded,dashed\"; color=\"#2a62c9\";\n");
+                let id = dot_subtree(&mut out, child, 1, opts, &mut next_id, "        ");
+                out.push_str("    }\n");
+                out.push_str(&format!("    n{} -> n{};\n", root, id));
+            } else {
+                let id = dot_subtree(&mut out, child, 1, opts, &mut next_id, "    ");
+                out.push_str(&format!("    n{} -> n{};\n", root, id));
+            }
+        }
+    }
+    out.push_str("}\n");
+    print!("{}", out);
+    Ok(())
+}
+
+fn dot_node(out: &mut String, node: &TreeNode, root_name: Option<&str>, depth: usize, opts: &GraphOptions, next_id: &mut usize, indent: &str) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let style = if node.is_dir {
+        "shape=folder, style=filled, fillcolor=\"#dbe8ff\", color=\"#2a62c9\""
+    } else {
+        "shape=note, style=filled, fillcolor=\"#f7f7f7\", color=\"#999999\""
+    };
+    out.push_str(&format!("{}n{} [label=\"{}\", {}];\n", indent, id, dot_escape(&label(node, root_name, depth, opts)), style));
+    id
+}
+
+fn dot_subtree(out: &mut String, node: &TreeNode, depth: usize, opts: &GraphOptions, next_id: &mut usize, indent: &str) -> usize {
+    let id = dot_node(out, node, None, depth, opts, next_id, indent);
+    if expand(depth, opts) {
+        for child in &node.children {
+            let child_id = dot_subtree(out, child, depth + 1, opts, next_id, indent);
+            out.push_str(&format!("{}n{} -> n{};\n", indent, id, child_id));
+        }
+    }
+    id
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn export_mermaid(tree: &TreeNode, root_name: &str, opts: &GraphOptions) -> std::io::Result<()> {
+    let mut out = String::new();
+    // fenced so the output can be pasted straight into a github readme
+    out.push_str("```mermaid\n");
+    out.push_str("flowchart LR\n");
+    let mut next_id = 0;
+    let mut edges = Vec::new();
+    let root = mermaid_node(&mut out, tree, Some(root_name), 0, opts, &mut next_id, "    ");
+    if expand(0, opts) {
+        for child in &tree.children {
+            if opts.cluster && child.is_dir {
+                out.push_str(&format!("    subgraph c{} [\"{}/\"]\n", next_id, mermaid_escape(&child.name)));
+                let id = mermaid_subtree(&mut out, child, 1, opts, &mut next_id, &mut edges, "        ");
+                out.push_str("    end\n");
+                edges.push((root, id));
+            } else {
+                let id = mermaid_subtree(&mut out, child, 1, opts, &mut next_id, &mut edges, "    ");
+                edges.push((root, id));
+            }
+        }
+    }
+    // edges go after every subgraph, mermaid gets confused by edges into a half-declared cluster
+    for (from, to) in edges {
+        out.push_str(&format!("    n{} --> n{}\n", from, to));
+    }
+    out.push_str("    classDef dir fill:#dbe8ff,stroke:#2a62c9,color:#1e1e1e\n");
+    out.push_str("    classDef file fill:#f7f7f7,stroke:#999999,color:#1e1e1e\n");
+    out.push_str("```\n");
+    print!("{}", out);
+    Ok(())
+}
+
+fn mermaid_node(out: &mut String, node: &TreeNode, root_name: Option<&str>, depth: usize, opts: &GraphOptions, next_id: &mut usize, indent: &str) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let text = mermaid_escape(&label(node, root_name, depth, opts));
+    // dirs get rounded boxes, files the plain ones
+    if node.is_dir {
+        out.push_str(&format!("{}n{}(\"{}\"):::dir\n", indent, id, text));
+    } else {
+        out.push_str(&format!("{}n{}[\"{}\"]:::file\n", indent, id, text));
+    }
+    id
+}
+
+fn mermaid_subtree(out: &mut String, node: &TreeNode, depth: usize, opts: &GraphOptions, next_id: &mut usize, edges: &mut Vec<(usize, usize)>, indent: &str) -> usize {
+    let id = mermaid_node(out, node, None, depth, opts, next_id, indent);
+    if expand(depth, opts) {
+        for child in &node.children {
+            let child_id = mermaid_subtree(out, child, depth + 1, opts, next_id, edges, indent);
+            edges.push((id, child_id));
+        }
+    }
+    id
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;")
+}
@@ -0,0 +1,108 @@
+use crate::{collect_files, ScanError, Special, TreeNode};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// content hashes, for telling apart files that have the same size
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HashAlgo {
+    Sha256,
+    Blake3,
+    Xxh3,
+}
+
+impl HashAlgo {
+    pub fn parse(s: &str) -> Option<HashAlgo> {
+        match s {
+            "sha256" | "sha-256" => Some(HashAlgo::Sha256),
+            "blake3" | "b3" => Some(HashAlgo::Blake3),
+            "xxh3" | "xxhash" => Some(HashAlgo::Xxh3),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Blake3 => "blake3",
+            HashAlgo::Xxh3 => "xxh3",
+        }
+    }
+}
+
+/// hex digest of a file, read in chunks so big files never sit in memory whole
+pub fn hash_file(path: &Path, algo: HashAlgo) -> io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut feed = |update: &mut dyn FnMut(&[u8])| -> io::Result<()> {
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            update(&buf[..n]);
+        }
+    };
+    match algo {
+        HashAlgo::Sha256 => {
+            let mut hasher = Sha256::new();
+            feed(&mut |b| hasher.update(b))?;
+            Ok(to_hex(&hasher.finalize()))
+        }
+        HashAlgo::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            feed(&mut |b| {
+                hasher.update(b);
+            })?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+        HashAlgo::Xxh3 => {
+            let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+            feed(&mut |b| hasher.update(b))?;
+            Ok(format!("{:016x}", hasher.digest()))
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `sha256:ab12...`, the algorithm goes with the digest so snapshots made with different ones don't get mixed up
+pub fn tagged(algo: HashAlgo, hex: &str) -> String {
+    format!("{}:{}", algo.name(), hex)
+}
+
+/// the algorithm and hex digest out of a `tagged` hash
+pub fn split_tagged(hash: &str) -> Option<(HashAlgo, &str)> {
+    let (algo, hex) = hash.split_once(':')?;
+    Some((HashAlgo::parse(algo)?, hex))
+}
+
+/// hash every file in the tree, spread over all cores. files over `max_size` are left without
+/// a hash, returns how many of those there were. a file that can't be read gets its `error` instead
+pub fn fill_hashes(tree: &mut TreeNode, root: &Path, algo: HashAlgo, max_size: Option<u64>) -> usize {
+    let mut jobs = Vec::new();
+    collect_files(tree, root.to_path_buf(), &mut jobs);
+    let too_big = AtomicUsize::new(0);
+    jobs.into_par_iter().for_each(|(node, path)| {
+        if node.special.is_some_and(Special::is_stream) {
+            return;
+        }
+        if max_size.is_some_and(|max| node.size.unwrap_or(0) > max) {
+            too_big.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        match hash_file(&path, algo) {
+            Ok(hex) => node.hash = Some(tagged(algo, &hex)),
+            // keep the first reason, a file whose metadata failed already says why
+            Err(e) => {
+                node.error.get_or_insert_with(|| ScanError::from_io(&e));
+            }
+        }
+    });
+    too_big.into_inner()
+}
@@ -0,0 +1,283 @@
+use crate::{escape_xml, TreeNode};
+
+// --export html: one self-contained page, the tree goes in as json and a bit of js
+// draws it, no cdn, no fonts, nothing external so it survives being attached to a ticket
+
+pub fn export_html(tree: &TreeNode, title: &str, si: bool) -> std::io::Result<()> {
+    let json = serde_json::to_string(tree)?;
+    // a file called </script> would end the script tag early, don't let it
+    let json = json.replace("</", "<\\/").replace("<!--", "<\\!--");
+    println!("{}", fill_template(&escape_xml(title), &json, si));
+    Ok(())
+}
+
+// one pass over the template, so a title or a file name that contains a placeholder stays plain text
+fn fill_template(title: &str, data: &str, si: bool) -> String {
+    let mut page = String::with_capacity(TEMPLATE.len() + data.len());
+    let mut rest = TEMPLATE;
+    while let Some(i) = rest.find("__CANOPY_") {
+        page.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let (value, placeholder) = if rest.starts_with("__CANOPY_TITLE__") {
+            (title, "__CANOPY_TITLE__")
+        } else if rest.starts_with("__CANOPY_DATA__") {
+            (data, "__CANOPY_DATA__")
+        } else if rest.starts_with("__CANOPY_SI__") {
+            (if si { "true" } else { "false" }, "__CANOPY_SI__")
+        } else {
+            ("__CANOPY_", "__CANOPY_")
+        };
+        page.push_str(value);
+        rest = &rest[placeholder.len()..];
+    }
+    page.push_str(rest);
+    page
+}
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="generator" content="canopy">
+<title>__CANOPY_TITLE__ - canopy</title>
+<style>
+:root { --bg: #fdfdfd; --fg: #1e1e1e; --muted: #777; --line: #e4e4e4; --dir: #2a62c9; --bar: #8fb3ee; --hit: #fff2a8; }
+@media (prefers-color-scheme: dark) {
+  :root { --bg: #16181d; --fg: #dcdcdc; --muted: #8a8a8a; --line: #2b2f36; --dir: #79a6ff; --bar: #3b5f9c; --hit: #5a4d00; }
+}
+* { box-sizing: border-box; }
+body { margin: 0; background: var(--bg); color: var(--fg); font: 14px/1.4 system-ui, -apple-system, "Segoe UI", sans-serif; }
+header { position: sticky; top: 0; background: var(--bg); border-bottom: 1px solid var(--line); padding: 10px 16px; display: flex; gap: 10px; align-items: center; flex-wrap: wrap; z-index: 1; }
+header h1 { font-size: 16px; margin: 0 12px 0 0; font-weight: 600; }
+header input { flex: 1; min-width: 200px; padding: 5px 8px; border: 1px solid var(--line); border-radius: 4px; background: var(--bg); color: var(--fg); }
+header button { padding: 5px 10px; border: 1px solid var(--line); border-radius: 4px; background: var(--bg); color: var(--fg); cursor: pointer; }
+#summary { color: var(--muted); font-size: 12px; }
+table { border-collapse: collapse; width: 100%; font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 13px; }
+th { text-align: left; padding: 6px 10px; border-bottom: 1px solid var(--line); cursor: pointer; user-select: none; white-space: nowrap; font-family: system-ui, sans-serif; }
+th.sorted::after { content: " \25BE"; }
+th.sorted.asc::after { content: " \25B4"; }
+td { padding: 2px 10px; white-space: nowrap; border-bottom: 1px solid var(--line); }
+td.name { width: 100%; }
+td.num { text-align: right; }
+tr.dir td.name span.label { color: var(--dir); font-weight: 600; cursor: pointer; }
+span.toggle { display: inline-block; width: 1.2em; color: var(--muted); cursor: pointer; }
+span.note { color: var(--muted); margin-left: 1em; }
+td.size { min-width: 170px; }
+div.bar { display: inline-block; height: 8px; background: var(--bar); border-radius: 2px; vertical-align: middle; margin-right: 6px; }
+div.barwrap { display: inline-block; width: 80px; }
+mark { background: var(--hit); color: inherit; }
+.muted { color: var(--muted); }
+</style>
+</head>
+<body>
+<header>
+  <h1>__CANOPY_TITLE__</h1>
+  <input id="search" type="search" placeholder="Search names..." autocomplete="off">
+  <button id="expand">Expand all</button>
+  <button id="collapse">Collapse all</button>
+  <span id="summary"></span>
+</header>
+<table>
+  <thead><tr id="head"></tr></thead>
+  <tbody id="rows"></tbody>
+</table>
+<script>
+"use strict";
+const tree = __CANOPY_DATA__;
+// --si, so the page shows the same units as the terminal
+const si = __CANOPY_SI__;
+
+let nextId = 0;
+function prepare(node, parent) {
+  node.id = nextId++;
+  node.parent = parent;
+  node.path = parent && parent.path ? parent.path + "/" + node.name : (parent ? node.name : "");
+  node.total = node.is_dir ? 0 : (node.size || 0);
+  node.count = node.is_dir ? 0 : 1;
+  for (const child of node.children) {
+    prepare(child, node);
+    node.total += child.total;
+    node.count += child.count;
+  }
+}
+prepare(tree, null);
+
+const hasMode = anyField(tree, "mode"), hasOwner = anyField(tree, "owner"), hasTime = anyField(tree, "mtime"), hasLoc = anyField(tree, "loc");
+function anyField(node, key) {
+  return node[key] != null || node.children.some(c => anyField(c, key));
+}
+
+const columns = [
+  { key: "name", label: "Name", value: n => n.name.toLowerCase() },
+  { key: "size", label: "Size", value: n => n.total },
+];
+if (hasTime) columns.push({ key: "mtime", label: "Modified", value: n => n.mtime || 0 });
+if (hasMode) columns.push({ key: "mode", label: "Permissions", value: n => n.mode || 0 });
+if (hasOwner) columns.push({ key: "owner", label: "Owner", value: n => (n.owner || "") + ":" + (n.group || "") });
+if (hasLoc) columns.push({ key: "loc", label: "Code lines", value: n => n.loc ? n.loc.code : -1 });
+
+let sortKey = "name", sortAsc = true, query = "";
+const collapsed = new Set();
+function collapseFrom(node, depth) {
+  if (node.is_dir && depth >= 2) collapsed.add(node.id);
+  node.children.forEach(c => collapseFrom(c, depth + 1));
+}
+collapseFrom(tree, 0);
+
+function formatSize(bytes) {
+  const base = si ? 1000 : 1024;
+  const units = si ? ["kB", "MB", "GB", "TB", "PB", "EB"] : ["K", "M", "G", "T", "P", "E"];
+  if (bytes < base) return bytes + "B";
+  let v = bytes / base, u = 0;
+  while (v >= base && u < units.length - 1) { v /= base; u++; }
+  return v.toFixed(1) + units[u];
+}
+function formatMode(mode) {
+  const kinds = { 0o040000: "d", 0o120000: "l", 0o010000: "p", 0o140000: "s", 0o020000: "c", 0o060000: "b" };
+  let s = kinds[mode & 0o170000] || "-";
+  const chars = "rwxrwxrwx";
+  for (let i = 0; i < 9; i++) s += (mode & (1 << (8 - i))) ? chars[i] : "-";
+  // setuid/setgid/sticky go where the x would be, same as the terminal shows them
+  const special = (bit, at, set) => {
+    if (mode & bit) s = s.slice(0, at) + (s[at] === "x" ? set : set.toUpperCase()) + s.slice(at + 1);
+  };
+  special(0o4000, 3, "s");
+  special(0o2000, 6, "s");
+  special(0o1000, 9, "t");
+  return s;
+}
+function formatTime(secs) {
+  const d = new Date(secs * 1000);
+  const pad = n => String(n).padStart(2, "0");
+  return d.getFullYear() + "-" + pad(d.getMonth() + 1) + "-" + pad(d.getDate()) + " " + pad(d.getHours()) + ":" + pad(d.getMinutes());
+}
+function el(tag, cls, text) {
+  const e = document.createElement(tag);
+  if (cls) e.className = cls;
+  if (text != null) e.textContent = text;
+  return e;
+}
+
+function matches(node) {
+  return query === "" || node.name.toLowerCase().includes(query);
+}
+// which nodes survive the search: matches plus everything above them
+function visibleSet() {
+  if (query === "") return null;
+  const keep = new Set();
+  (function walk(node) {
+    let any = matches(node) && node !== tree;
+    for (const c of node.children) any = walk(c) || any;
+    if (any) keep.add(node.id);
+    return any;
+  })(tree);
+  keep.add(tree.id);
+  return keep;
+}
+
+function sortedChildren(node) {
+  const col = columns.find(c => c.key === sortKey);
+  const kids = node.children.slice();
+  kids.sort((a, b) => {
+    if (sortKey === "name" && a.is_dir !== b.is_dir) return a.is_dir ? -1 : 1;
+    const x = col.value(a), y = col.value(b);
+    const cmp = x < y ? -1 : x > y ? 1 : 0;
+    return sortAsc ? cmp : -cmp;
+  });
+  return kids;
+}
+
+function highlight(target, name) {
+  if (query === "") { target.textContent = name; return; }
+  const at = name.toLowerCase().indexOf(query);
+  if (at < 0) { target.textContent = name; return; }
+  target.append(name.slice(0, at), el("mark", null, name.slice(at, at + query.length)), name.slice(at + query.length));
+}
+
+function render() {
+  const head = document.getElementById("head");
+  head.replaceChildren();
+  for (const col of columns) {
+    const th = el("th", col.key === sortKey ? "sorted" + (sortAsc ? " asc" : "") : "", col.label);
+    th.onclick = () => {
+      if (sortKey === col.key) sortAsc = !sortAsc;
+      else { sortKey = col.key; sortAsc = col.key === "name"; }
+      render();
+    };
+    head.append(th);
+  }
+  const keep = visibleSet();
+  const body = document.getElementById("rows");
+  const frag = document.createDocumentFragment();
+  let shown = 0;
+  (function walk(node, depth) {
+    if (keep && !keep.has(node.id)) return;
+    shown++;
+    const tr = el("tr", node.is_dir ? "dir" : "file");
+    const name = el("td", "name");
+    name.style.paddingLeft = (10 + depth * 18) + "px";
+    const open = !collapsed.has(node.id) || keep !== null;
+    const toggle = el("span", "toggle", node.is_dir && node.children.length ? (open ? "▾" : "▸") : "");
+    const label = el("span", "label");
+    highlight(label, node === tree ? (node.name || ".") : node.name + (node.is_dir ? "/" : ""));
+    if (node.is_dir) {
+      const flip = () => { collapsed.has(node.id) ? collapsed.delete(node.id) : collapsed.add(node.id); render(); };
+      toggle.onclick = flip;
+      label.onclick = flip;
+      label.title = node.count + " files";
+    }
+    name.append(toggle, label);
+    if (node.note) name.append(el("span", "note", "# " + node.note));
+    tr.append(name);
+    for (const col of columns.slice(1)) {
+      const td = el("td", col.key === "size" ? "size num" : col.key === "loc" ? "muted num" : "muted");
+      if (col.key === "size") {
+        const parentTotal = node.parent ? node.parent.total : node.total;
+        const wrap = el("div", "barwrap");
+        const bar = el("div", "bar");
+        bar.style.width = (parentTotal > 0 ? Math.max(1, Math.round(80 * node.total / parentTotal)) : 0) + "px";
+        wrap.append(bar);
+        td.append(wrap, formatSize(node.total));
+        td.title = node.total.toLocaleString() + " bytes";
+      } else if (col.key === "mtime") {
+        td.textContent = node.mtime != null ? formatTime(node.mtime) : "-";
+      } else if (col.key === "mode") {
+        td.textContent = node.mode != null ? formatMode(node.mode) : "-";
+      } else if (col.key === "owner") {
+        td.textContent = node.owner != null ? node.owner + ":" + (node.group || "") : "-";
+      } else if (col.key === "loc") {
+        td.textContent = node.loc ? node.loc.code.toLocaleString() : "-";
+        if (node.loc) td.title = node.loc.lines + " lines: " + node.loc.code + " code, " + node.loc.comments + " comments, " + node.loc.blank + " blank";
+      }
+      tr.append(td);
+    }
+    frag.append(tr);
+    if (node.is_dir && open) sortedChildren(node).forEach(c => walk(c, depth + 1));
+  })(tree, 0);
+  body.replaceChildren(frag);
+  document.getElementById("summary").textContent =
+    tree.count.toLocaleString() + " files, " + formatSize(tree.total) + (keep ? " — " + (shown - 1) + " shown" : "");
+}
+
+document.getElementById("search").addEventListener("input", e => { query = e.target.value.trim().toLowerCase(); render(); });
+document.getElementById("expand").onclick = () => { collapsed.clear(); render(); };
+document.getElementById("collapse").onclick = () => { collapsed.clear(); collapseFrom(tree, 1); render(); };
+render();
+</script>
+</body>
+</html>"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_in_names_stay_plain() {
+        let page = fill_template("__CANOPY_DATA__", r#"{"name":"__CANOPY_SI__"}"#, true);
+        assert!(page.contains("<title>__CANOPY_DATA__ - canopy</title>"));
+        assert!(page.contains(r#"const tree = {"name":"__CANOPY_SI__"};"#));
+        assert!(page.contains("const si = true;"));
+        assert!(!page.contains("__CANOPY_TITLE__"));
+    }
+}
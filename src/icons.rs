@@ -0,0 +1,564 @@
+use crate::config::{IconConfig, IconOverrides};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// one registry for every file kind canopy knows about, the tree printer and the tui
+// both go through here so they can't drift apart again
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Category {
+    Code,
+    Script,
+    Markup,
+    Style,
+    Config,
+    Data,
+    Document,
+    Text,
+    Image,
+    Audio,
+    Video,
+    Archive,
+    Executable,
+    Font,
+    Database,
+    Lock,
+    Vcs,
+    Build,
+    Key,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Code => "code",
+            Category::Script => "script",
+            Category::Markup => "markup",
+            Category::Style => "style",
+            Category::Config => "config",
+            Category::Data => "data",
+            Category::Document => "document",
+            Category::Text => "text",
+            Category::Image => "image",
+            Category::Audio => "audio",
+            Category::Video => "video",
+            Category::Archive => "archive",
+            Category::Executable => "executable",
+            Category::Font => "font",
+            Category::Database => "database",
+            Category::Lock => "lock",
+            Category::Vcs => "vcs",
+            Category::Build => "build",
+            Category::Key => "key",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FileKind {
+    pub name: &'static str,
+    pub category: Category,
+    pub nerd: &'static str,
+    pub emoji: &'static str,
+}
+
+const fn kind(name: &'static str, category: Category, nerd: &'static str, emoji: &'static str) -> FileKind {
+    FileKind { name, category, nerd, emoji }
+}
+
+use Category::*;
+
+// === languages === //
+pub static RUST: FileKind = kind("Rust", Code, "\u{e7a8}", "🦀");
+pub static PYTHON: FileKind = kind("Python", Code, "\u{e606}", "🐍");
+pub static JAVASCRIPT: FileKind = kind("JavaScript", Code, "\u{e74e}", "📜");
+pub static TYPESCRIPT: FileKind = kind("TypeScript", Code, "\u{e628}", "📘");
+pub static REACT: FileKind = kind("React", Code, "\u{e7ba}", "⚛");
+pub static C: FileKind = kind("C", Code, "\u{e61e}", "🇨");
+pub static CPP: FileKind = kind("C++", Code, "\u{e61d}", "➕");
+pub static C_HEADER: FileKind = kind("C Header", Code, "\u{f0fd}", "🇭");
+pub static CSHARP: FileKind = kind("C#", Code, "\u{f031b}", "🎼");
+pub static FSHARP: FileKind = kind("F#", Code, "\u{e7a7}", "🎼");
+pub static GO: FileKind = kind("Go", Code, "\u{e627}", "🐹");
+pub static JAVA: FileKind = kind("Java", Code, "\u{e738}", "☕");
+pub static KOTLIN: FileKind = kind("Kotlin", Code, "\u{e634}", "🟣");
+pub static SCALA: FileKind = kind("Scala", Code, "\u{e737}", "🔺");
+pub static GROOVY: FileKind = kind("Groovy", Code, "\u{e775}", "⭐");
+pub static CLOJURE: FileKind = kind("Clojure", Code, "\u{e768}", "🌀");
+pub static SWIFT: FileKind = kind("Swift", Code, "\u{e755}", "🐦");
+pub static OBJC: FileKind = kind("Objective-C", Code, "\u{e61e}", "🍎");
+pub static RUBY: FileKind = kind("Ruby", Code, "\u{e739}", "💎");
+pub static PHP: FileKind = kind("PHP", Code, "\u{e73d}", "🐘");
+pub static PERL: FileKind = kind("Perl", Code, "\u{e769}", "🐪");
+pub static LUA: FileKind = kind("Lua", Code, "\u{e620}", "🌙");
+pub static HASKELL: FileKind = kind("Haskell", Code, "\u{e777}", "λ");
+pub static OCAML: FileKind = kind("OCaml", Code, "\u{e67a}", "🐫");
+pub static ELIXIR: FileKind = kind("Elixir", Code, "\u{e62d}", "💧");
+pub static ERLANG: FileKind = kind("Erlang", Code, "\u{e7b1}", "📡");
+pub static DART: FileKind = kind("Dart", Code, "\u{e798}", "🎯");
+pub static R: FileKind = kind("R", Code, "\u{f25d}", "📈");
+pub static JULIA: FileKind = kind("Julia", Code, "\u{e624}", "🔴");
+pub static ZIG: FileKind = kind("Zig", Code, "\u{e6a9}", "⚡");
+pub static NIM: FileKind = kind("Nim", Code, "\u{e677}", "👑");
+pub static D: FileKind = kind("D", Code, "\u{e7af}", "🇩");
+pub static V: FileKind = kind("V", Code, "\u{e6ac}", "🇻");
+pub static CRYSTAL: FileKind = kind("Crystal", Code, "\u{e62f}", "🔮");
+pub static FORTRAN: FileKind = kind("Fortran", Code, "\u{f121}", "🧮");
+pub static ASSEMBLY: FileKind = kind("Assembly", Code, "\u{e637}", "🔩");
+pub static LISP: FileKind = kind("Lisp", Code, "\u{f121}", "🌀");
+pub static ELM: FileKind = kind("Elm", Code, "\u{e62c}", "🌳");
+pub static PURESCRIPT: FileKind = kind("PureScript", Code, "\u{e630}", "🔷");
+pub static SOLIDITY: FileKind = kind("Solidity", Code, "\u{e656}", "💠");
+pub static VHDL: FileKind = kind("VHDL", Code, "\u{f2db}", "🔌");
+pub static VERILOG: FileKind = kind("Verilog", Code, "\u{f2db}", "🔌");
+pub static PASCAL: FileKind = kind("Pascal", Code, "\u{f121}", "📐");
+pub static ADA: FileKind = kind("Ada", Code, "\u{f121}", "📐");
+pub static COBOL: FileKind = kind("COBOL", Code, "\u{f121}", "🦕");
+pub static VB: FileKind = kind("Visual Basic", Code, "\u{f121}", "📘");
+pub static GLSL: FileKind = kind("Shader", Code, "\u{f0ad}", "✨");
+pub static WASM: FileKind = kind("WebAssembly", Code, "\u{e6a1}", "🧩");
+pub static SQL: FileKind = kind("SQL", Code, "\u{e706}", "💾");
+pub static PROTO: FileKind = kind("Protobuf", Code, "\u{f1c9}", "📡");
+pub static GRAPHQL: FileKind = kind("GraphQL", Code, "\u{e662}", "🔗");
+pub static VIM: FileKind = kind("Vim script", Code, "\u{e62b}", "📗");
+pub static NIX: FileKind = kind("Nix", Code, "\u{f313}", "❄");
+pub static TERRAFORM: FileKind = kind("Terraform", Code, "\u{e69a}", "🏗");
+
+// === scripts === //
+pub static SHELL: FileKind = kind("Shell", Script, "\u{f489}", "🐚");
+pub static POWERSHELL: FileKind = kind("PowerShell", Script, "\u{e795}", "🐚");
+pub static BATCH: FileKind = kind("Batch", Script, "\u{e795}", "🐚");
+
+// === web / markup / styles === //
+pub static HTML: FileKind = kind("HTML", Markup, "\u{e736}", "🌐");
+pub static XML: FileKind = kind("XML", Markup, "\u{f05c0}", "📰");
+pub static SVG: FileKind = kind("SVG", Image, "\u{f0721}", "🎨");
+pub static VUE: FileKind = kind("Vue", Markup, "\u{e6a0}", "💚");
+pub static SVELTE: FileKind = kind("Svelte", Markup, "\u{e697}", "🧡");
+pub static TEMPLATE: FileKind = kind("Template", Markup, "\u{f121}", "🧾");
+pub static CSS: FileKind = kind("CSS", Style, "\u{e749}", "🎨");
+pub static SASS: FileKind = kind("Sass", Style, "\u{e603}", "🎨");
+pub static LESS: FileKind = kind("Less", Style, "\u{e758}", "🎨");
+pub static MARKDOWN: FileKind = kind("Markdown", Document, "\u{f48a}", "📝");
+pub static RST: FileKind = kind("reStructuredText", Document, "\u{f15c}", "📝");
+pub static TEX: FileKind = kind("TeX", Document, "\u{e69b}", "📐");
+
+// === config / data === //
+pub static JSON: FileKind = kind("JSON", Data, "\u{e60b}", "🔧");
+pub static YAML: FileKind = kind("YAML", Config, "\u{e6a8}", "🔧");
+pub static TOML: FileKind = kind("TOML", Config, "\u{e6b2}", "🔧");
+pub static INI: FileKind = kind("INI", Config, "\u{f013}", "🔧");
+pub static ENV: FileKind = kind("Env", Config, "\u{f462}", "🔧");
+pub static CSV: FileKind = kind("CSV", Data, "\u{f1c3}", "📊");
+pub static DATABASE: FileKind = kind("Database", Database, "\u{f1c0}", "💾");
+pub static LOG: FileKind = kind("Log", Text, "\u{f18d}", "📋");
+pub static DIFF: FileKind = kind("Diff", Text, "\u{f440}", "🩹");
+pub static TEXT: FileKind = kind("Text", Text, "\u{f15c}", "📄");
+pub static LICENSE: FileKind = kind("License", Document, "\u{f0219}", "⚖");
+pub static README: FileKind = kind("Readme", Document, "\u{f00ba}", "📖");
+
+// === office / documents === //
+pub static PDF: FileKind = kind("PDF", Document, "\u{f1c1}", "📕");
+pub static WORD: FileKind = kind("Word", Document, "\u{f1c2}", "📘");
+pub static SPREADSHEET: FileKind = kind("Spreadsheet", Document, "\u{f1c3}", "📊");
+pub static PRESENTATION: FileKind = kind("Presentation", Document, "\u{f1c4}", "📙");
+pub static EBOOK: FileKind = kind("E-book", Document, "\u{e28b}", "📚");
+
+// === media === //
+pub static IMAGE: FileKind = kind("Image", Image, "\u{f1c5}", "📷");
+pub static AUDIO: FileKind = kind("Audio", Audio, "\u{f1c7}", "🎵");
+pub static VIDEO: FileKind = kind("Video", Video, "\u{f1c8}", "🎬");
+pub static FONT: FileKind = kind("Font", Font, "\u{f031}", "🔤");
+pub static MODEL3D: FileKind = kind("3D Model", Image, "\u{f1b2}", "🧊");
+
+// === archives / binaries === //
+pub static ARCHIVE: FileKind = kind("Archive", Archive, "\u{f410}", "📦");
+pub static DISK_IMAGE: FileKind = kind("Disk Image", Archive, "\u{f0a0}", "💿");
+pub static PACKAGE: FileKind = kind("Package", Archive, "\u{f487}", "📦");
+pub static EXECUTABLE: FileKind = kind("Executable", Executable, "\u{f489}", "⚡");
+pub static LIBRARY: FileKind = kind("Library", Executable, "\u{f1b3}", "🧱");
+pub static OBJECT: FileKind = kind("Object", Executable, "\u{f1b3}", "🧱");
+
+// === tooling === //
+pub static LOCK: FileKind = kind("Lockfile", Lock, "\u{f023}", "🔒");
+pub static KEY: FileKind = kind("Key", Key, "\u{f084}", "🔑");
+pub static CERT: FileKind = kind("Certificate", Key, "\u{f0a3}", "🔏");
+pub static GIT: FileKind = kind("Git", Vcs, "\u{f1d3}", "🌱");
+pub static DOCKER: FileKind = kind("Docker", Build, "\u{f308}", "🐳");
+pub static MAKE: FileKind = kind("Makefile", Build, "\u{e779}", "🔨");
+pub static CMAKE: FileKind = kind("CMake", Build, "\u{e794}", "🔨");
+pub static CARGO: FileKind = kind("Cargo", Build, "\u{e7a8}", "📦");
+pub static NPM: FileKind = kind("npm", Build, "\u{e71e}", "📦");
+pub static GRADLE: FileKind = kind("Gradle", Build, "\u{e660}", "🐘");
+pub static MAVEN: FileKind = kind("Maven", Build, "\u{e674}", "🪶");
+pub static CI: FileKind = kind("CI", Build, "\u{f013}", "🤖");
+pub static EDITOR: FileKind = kind("Editor config", Config, "\u{e615}", "🔧");
+
+// folders get their own little table, only the really common ones
+pub static DIR: FileKind = kind("Directory", Config, "\u{f07b}", "📁");
+static DIR_GIT: FileKind = kind("Git folder", Vcs, "\u{e5fb}", "🌱");
+static DIR_GITHUB: FileKind = kind("GitHub folder", Vcs, "\u{e5fd}", "🐙");
+static DIR_NODE: FileKind = kind("node_modules", Build, "\u{e5fa}", "📦");
+static DIR_SRC: FileKind = kind("Source folder", Code, "\u{f19fc}", "📂");
+static DIR_BUILD: FileKind = kind("Build output", Build, "\u{f487}", "🏗");
+static DIR_DOCS: FileKind = kind("Docs folder", Document, "\u{f02d}", "📚");
+static DIR_TESTS: FileKind = kind("Tests folder", Code, "\u{f0668}", "🧪");
+static DIR_CONFIG: FileKind = kind("Config folder", Config, "\u{e5fc}", "🔧");
+
+static EXTENSIONS: &[(&str, &FileKind)] = &[
+    ("rs", &RUST),
+    ("py", &PYTHON), ("pyw", &PYTHON), ("pyi", &PYTHON), ("pyx", &PYTHON), ("pxd", &PYTHON), ("ipynb", &PYTHON),
+    ("js", &JAVASCRIPT), ("mjs", &JAVASCRIPT), ("cjs", &JAVASCRIPT),
+    ("ts", &TYPESCRIPT), ("mts", &TYPESCRIPT), ("cts", &TYPESCRIPT),
+    ("jsx", &REACT), ("tsx", &REACT),
+    ("c", &C),
+    ("cpp", &CPP), ("cc", &CPP), ("cxx", &CPP), ("c++", &CPP), ("ino", &CPP),
+    ("h", &C_HEADER), ("hpp", &C_HEADER), ("hh", &C_HEADER), ("hxx", &C_HEADER), ("inl", &C_HEADER),
+    ("cs", &CSHARP), ("csx", &CSHARP), ("csproj", &CSHARP), ("sln", &CSHARP),
+    ("fs", &FSHARP), ("fsi", &FSHARP), ("fsx", &FSHARP), ("fsproj", &FSHARP),
+    ("go", &GO),
+    ("java", &JAVA), ("class", &JAVA), ("jar", &JAVA), ("war", &JAVA),
+    ("kt", &KOTLIN), ("kts", &KOTLIN),
+    ("scala", &SCALA), ("sc", &SCALA), ("sbt", &SCALA),
+    ("groovy", &GROOVY), ("gvy", &GROOVY),
+    ("clj", &CLOJURE), ("cljs", &CLOJURE), ("cljc", &CLOJURE), ("edn", &CLOJURE),
+    ("swift", &SWIFT),
+    ("m", &OBJC), ("mm", &OBJC),
+    ("rb", &RUBY), ("erb", &RUBY), ("gemspec", &RUBY), ("rake", &RUBY),
+    ("php", &PHP), ("phtml", &PHP),
+    ("pl", &PERL), ("pm", &PERL), ("t", &PERL),
+    ("lua", &LUA), ("luau", &LUA),
+    ("hs", &HASKELL), ("lhs", &HASKELL),
+    ("ml", &OCAML), ("mli", &OCAML),
+    ("ex", &ELIXIR), ("exs", &ELIXIR), ("heex", &ELIXIR),
+    ("erl", &ERLANG), ("hrl", &ERLANG),
+    ("dart", &DART),
+    ("r", &R), ("rmd", &R),
+    ("jl", &JULIA),
+    ("zig", &ZIG),
+    ("nim", &NIM), ("nims", &NIM),
+    ("d", &D),
+    ("v", &V),
+    ("cr", &CRYSTAL),
+    ("f", &FORTRAN), ("f90", &FORTRAN), ("f95", &FORTRAN), ("for", &FORTRAN),
+    ("asm", &ASSEMBLY), ("s", &ASSEMBLY), ("nasm", &ASSEMBLY),
+    ("lisp", &LISP), ("lsp", &LISP), ("el", &LISP), ("scm", &LISP), ("rkt", &LISP),
+    ("elm", &ELM),
+    ("purs", &PURESCRIPT),
+    ("sol", &SOLIDITY),
+    ("vhdl", &VHDL),
+    ("sv", &VERILOG), ("svh", &VERILOG),
+    ("pas", &PASCAL), ("pp", &PASCAL),
+    ("adb", &ADA), ("ads", &ADA),
+    ("cob", &COBOL), ("cbl", &COBOL),
+    ("vb", &VB), ("vbs", &VB), ("bas", &VB),
+    ("glsl", &GLSL), ("vert", &GLSL), ("frag", &GLSL), ("hlsl", &GLSL), ("wgsl", &GLSL), ("shader", &GLSL),
+    ("wasm", &WASM), ("wat", &WASM),
+    ("sql", &SQL), ("psql", &SQL), ("mysql", &SQL),
+    ("proto", &PROTO),
+    ("graphql", &GRAPHQL), ("gql", &GRAPHQL),
+    ("vim", &VIM),
+    ("nix", &NIX),
+    ("tf", &TERRAFORM), ("tfvars", &TERRAFORM), ("hcl", &TERRAFORM),
+    ("sh", &SHELL), ("bash", &SHELL), ("zsh", &SHELL), ("fish", &SHELL), ("ksh", &SHELL), ("csh", &SHELL),
+    ("ps1", &POWERSHELL), ("psm1", &POWERSHELL), ("psd1", &POWERSHELL),
+    ("bat", &BATCH), ("cmd", &BATCH),
+    ("html", &HTML), ("htm", &HTML), ("xhtml", &HTML),
+    ("xml", &XML), ("xsd", &XML), ("xsl", &XML), ("xslt", &XML), ("plist", &XML), ("rss", &XML), ("atom", &XML),
+    ("svg", &SVG),
+    ("vue", &VUE),
+    ("svelte", &SVELTE),
+    ("hbs", &TEMPLATE), ("mustache", &TEMPLATE), ("jinja", &TEMPLATE), ("j2", &TEMPLATE), ("njk", &TEMPLATE),
+    ("twig", &TEMPLATE), ("liquid", &TEMPLATE), ("ejs", &TEMPLATE), ("pug", &TEMPLATE), ("haml", &TEMPLATE),
+    ("tera", &TEMPLATE), ("hjs", &TEMPLATE),
+    ("css", &CSS),
+    ("scss", &SASS), ("sass", &SASS),
+    ("less", &LESS), ("styl", &LESS),
+    ("md", &MARKDOWN), ("markdown", &MARKDOWN), ("mdx", &MARKDOWN),
+    ("rst", &RST), ("adoc", &RST), ("asciidoc", &RST), ("org", &RST),
+    ("tex", &TEX), ("sty", &TEX), ("cls", &TEX), ("bib", &TEX),
+    ("json", &JSON), ("jsonc", &JSON), ("json5", &JSON), ("jsonl", &JSON), ("ndjson", &JSON), ("geojson", &JSON),
+    ("yaml", &YAML), ("yml", &YAML),
+    ("toml", &TOML),
+    ("ini", &INI), ("cfg", &INI), ("conf", &INI), ("config", &INI), ("properties", &INI), ("reg", &INI),
+    ("env", &ENV),
+    ("csv", &CSV), ("tsv", &CSV), ("parquet", &CSV), ("arrow", &CSV), ("avro", &CSV),
+    ("db", &DATABASE), ("sqlite", &DATABASE), ("sqlite3", &DATABASE), ("mdb", &DATABASE), ("accdb", &DATABASE),
+    ("dump", &DATABASE), ("rdb", &DATABASE),
+    ("log", &LOG), ("out", &LOG),
+    ("diff", &DIFF), ("patch", &DIFF),
+    ("txt", &TEXT), ("text", &TEXT), ("nfo", &TEXT),
+    ("pdf", &PDF),
+    ("doc", &WORD), ("docx", &WORD), ("odt", &WORD), ("rtf", &WORD), ("pages", &WORD),
+    ("xls", &SPREADSHEET), ("xlsx", &SPREADSHEET), ("xlsm", &SPREADSHEET), ("ods", &SPREADSHEET), ("numbers", &SPREADSHEET),
+    ("ppt", &PRESENTATION), ("pptx", &PRESENTATION), ("odp", &PRESENTATION), ("key", &PRESENTATION),
+    ("epub", &EBOOK), ("mobi", &EBOOK), ("azw3", &EBOOK), ("djvu", &EBOOK),
+    ("png", &IMAGE), ("jpg", &IMAGE), ("jpeg", &IMAGE), ("gif", &IMAGE), ("bmp", &IMAGE), ("webp", &IMAGE),
+    ("ico", &IMAGE), ("tif", &IMAGE), ("tiff", &IMAGE), ("avif", &IMAGE), ("heic", &IMAGE), ("heif", &IMAGE),
+    ("psd", &IMAGE), ("xcf", &IMAGE), ("raw", &IMAGE), ("cr2", &IMAGE), ("nef", &IMAGE), ("dng", &IMAGE),
+    ("ai", &IMAGE), ("eps", &IMAGE), ("jxl", &IMAGE), ("qoi", &IMAGE), ("tga", &IMAGE), ("exr", &IMAGE), ("hdr", &IMAGE),
+    ("mp3", &AUDIO), ("wav", &AUDIO), ("flac", &AUDIO), ("ogg", &AUDIO), ("oga", &AUDIO), ("opus", &AUDIO),
+    ("m4a", &AUDIO), ("aac", &AUDIO), ("wma", &AUDIO), ("aiff", &AUDIO), ("mid", &AUDIO), ("midi", &AUDIO),
+    ("mp4", &VIDEO), ("mkv", &VIDEO), ("webm", &VIDEO), ("avi", &VIDEO), ("mov", &VIDEO), ("wmv", &VIDEO),
+    ("flv", &VIDEO), ("m4v", &VIDEO), ("mpg", &VIDEO), ("mpeg", &VIDEO), ("ogv", &VIDEO), ("3gp", &VIDEO),
+    ("ttf", &FONT), ("otf", &FONT), ("woff", &FONT), ("woff2", &FONT), ("eot", &FONT), ("fon", &FONT),
+    ("obj", &MODEL3D), ("fbx", &MODEL3D), ("stl", &MODEL3D), ("blend", &MODEL3D), ("gltf", &MODEL3D), ("glb", &MODEL3D),
+    ("zip", &ARCHIVE), ("tar", &ARCHIVE), ("gz", &ARCHIVE), ("tgz", &ARCHIVE), ("bz2", &ARCHIVE), ("tbz2", &ARCHIVE),
+    ("xz", &ARCHIVE), ("txz", &ARCHIVE), ("zst", &ARCHIVE), ("tzst", &ARCHIVE), ("lz", &ARCHIVE), ("lz4", &ARCHIVE),
+    ("lzma", &ARCHIVE), ("7z", &ARCHIVE), ("rar", &ARCHIVE), ("cab", &ARCHIVE), ("z", &ARCHIVE),
+    ("iso", &DISK_IMAGE), ("img", &DISK_IMAGE), ("dmg", &DISK_IMAGE), ("vhd", &DISK_IMAGE), ("vhdx", &DISK_IMAGE),
+    ("vmdk", &DISK_IMAGE), ("qcow2", &DISK_IMAGE),
+    ("deb", &PACKAGE), ("rpm", &PACKAGE), ("apk", &PACKAGE), ("msi", &PACKAGE), ("appimage", &PACKAGE),
+    ("flatpak", &PACKAGE), ("snap", &PACKAGE), ("pkg", &PACKAGE), ("crate", &PACKAGE), ("whl", &PACKAGE),
+    ("gem", &PACKAGE), ("nupkg", &PACKAGE), ("vsix", &PACKAGE),
+    ("exe", &EXECUTABLE), ("com", &EXECUTABLE), ("bin", &EXECUTABLE), ("elf", &EXECUTABLE), ("app", &EXECUTABLE),
+    ("dll", &LIBRARY), ("so", &LIBRARY), ("dylib", &LIBRARY), ("a", &LIBRARY), ("lib", &LIBRARY), ("rlib", &LIBRARY),
+    ("o", &OBJECT), ("pdb", &OBJECT), ("pyc", &OBJECT), ("pyo", &OBJECT), ("beam", &OBJECT),
+    ("lock", &LOCK),
+    ("pem", &KEY), ("pub", &KEY), ("gpg", &KEY), ("asc", &KEY), ("sig", &KEY),
+    ("crt", &CERT), ("cer", &CERT), ("der", &CERT), ("p12", &CERT), ("pfx", &CERT), ("csr", &CERT),
+    ("dockerfile", &DOCKER),
+    ("mk", &MAKE), ("mak", &MAKE),
+    ("cmake", &CMAKE),
+    ("gradle", &GRADLE),
+    ("editorconfig", &EDITOR),
+];
+
+static FILENAMES: &[(&str, &FileKind)] = &[
+    ("Cargo.toml", &CARGO), ("Cargo.lock", &LOCK), ("rust-toolchain", &RUST), ("rust-toolchain.toml", &RUST),
+    ("rustfmt.toml", &RUST), (".rustfmt.toml", &RUST), ("clippy.toml", &RUST), ("build.rs", &CARGO),
+    ("package.json", &NPM), ("package-lock.json", &LOCK), (".npmrc", &NPM), (".npmignore", &NPM), (".nvmrc", &NPM),
+    ("yarn.lock", &LOCK), ("pnpm-lock.yaml", &LOCK), ("bun.lockb", &LOCK), ("pnpm-workspace.yaml", &NPM),
+    ("tsconfig.json", &TYPESCRIPT), ("jsconfig.json", &JAVASCRIPT), (".eslintrc", &EDITOR), (".eslintrc.json", &EDITOR),
+    (".eslintrc.js", &EDITOR), ("eslint.config.js", &EDITOR), (".prettierrc", &EDITOR), (".prettierignore", &EDITOR),
+    ("webpack.config.js", &NPM), ("vite.config.ts", &NPM), ("vite.config.js", &NPM), ("babel.config.js", &NPM),
+    ("Dockerfile", &DOCKER), ("dockerfile", &DOCKER), ("Containerfile", &DOCKER), (".dockerignore", &DOCKER),
+    ("docker-compose.yml", &DOCKER), ("docker-compose.yaml", &DOCKER), ("compose.yml", &DOCKER), ("compose.yaml", &DOCKER),
+    (".gitignore", &GIT), (".gitattributes", &GIT), (".gitmodules", &GIT), (".gitkeep", &GIT), (".mailmap", &GIT),
+    ("CODEOWNERS", &GIT), (".git-blame-ignore-revs", &GIT),
+    ("Makefile", &MAKE), ("makefile", &MAKE), ("GNUmakefile", &MAKE), ("Justfile", &MAKE), ("justfile", &MAKE),
+    ("Rakefile", &RUBY), ("Gemfile", &RUBY), ("Gemfile.lock", &LOCK), (".ruby-version", &RUBY),
+    ("CMakeLists.txt", &CMAKE), ("meson.build", &MAKE), ("BUILD", &MAKE), ("BUILD.bazel", &MAKE), ("WORKSPACE", &MAKE),
+    ("build.gradle", &GRADLE), ("build.gradle.kts", &GRADLE), ("settings.gradle", &GRADLE), ("gradlew", &GRADLE),
+    ("gradlew.bat", &GRADLE), ("pom.xml", &MAVEN),
+    ("go.mod", &GO), ("go.sum", &LOCK), ("go.work", &GO),
+    ("requirements.txt", &PYTHON), ("pyproject.toml", &PYTHON), ("setup.py", &PYTHON), ("setup.cfg", &PYTHON),
+    ("Pipfile", &PYTHON), ("Pipfile.lock", &LOCK), ("poetry.lock", &LOCK), ("uv.lock", &LOCK), ("tox.ini", &PYTHON),
+    (".python-version", &PYTHON), ("composer.json", &PHP), ("composer.lock", &LOCK), ("mix.exs", &ELIXIR),
+    ("mix.lock", &LOCK), ("flake.nix", &NIX), ("flake.lock", &LOCK), ("shell.nix", &NIX), ("default.nix", &NIX),
+    ("LICENSE", &LICENSE), ("LICENSE.md", &LICENSE), ("LICENSE.txt", &LICENSE), ("LICENCE", &LICENSE),
+    ("COPYING", &LICENSE), ("UNLICENSE", &LICENSE),
+    ("README", &README), ("README.md", &README), ("README.txt", &README), ("README.rst", &README),
+    ("CHANGELOG", &MARKDOWN), ("CHANGELOG.md", &MARKDOWN), ("CONTRIBUTING.md", &MARKDOWN), ("AUTHORS", &TEXT),
+    ("SECURITY.md", &MARKDOWN), ("CODE_OF_CONDUCT.md", &MARKDOWN), ("FUNDING.yml", &YAML),
+    (".editorconfig", &EDITOR), (".env", &ENV), (".env.local", &ENV), (".env.example", &ENV), (".envrc", &ENV),
+    (".bashrc", &SHELL), (".bash_profile", &SHELL), (".zshrc", &SHELL), (".zprofile", &SHELL), (".profile", &SHELL),
+    (".vimrc", &VIM), ("vimrc", &VIM), (".tmux.conf", &SHELL), (".inputrc", &SHELL),
+    (".travis.yml", &CI), (".gitlab-ci.yml", &CI), ("Jenkinsfile", &CI), ("azure-pipelines.yml", &CI),
+    (".pre-commit-config.yaml", &CI), ("Vagrantfile", &RUBY), ("Procfile", &CI),
+    ("id_rsa", &KEY), ("id_ed25519", &KEY), ("id_ecdsa", &KEY), ("authorized_keys", &KEY), ("known_hosts", &KEY),
+    (".DS_Store", &TEXT), ("Thumbs.db", &DATABASE), ("desktop.ini", &INI),
+];
+
+static DIRNAMES: &[(&str, &FileKind)] = &[
+    (".git", &DIR_GIT), (".github", &DIR_GITHUB), (".gitlab", &DIR_GITHUB),
+    ("node_modules", &DIR_NODE),
+    ("src", &DIR_SRC), ("lib", &DIR_SRC), ("source", &DIR_SRC),
+    ("target", &DIR_BUILD), ("build", &DIR_BUILD), ("dist", &DIR_BUILD), ("out", &DIR_BUILD), ("bin", &DIR_BUILD),
+    ("docs", &DIR_DOCS), ("doc", &DIR_DOCS),
+    ("test", &DIR_TESTS), ("tests", &DIR_TESTS), ("spec", &DIR_TESTS), ("__tests__", &DIR_TESTS),
+    (".config", &DIR_CONFIG), ("config", &DIR_CONFIG), (".vscode", &DIR_CONFIG), (".idea", &DIR_CONFIG),
+];
+
+struct Registry {
+    extensions: HashMap<&'static str, &'static FileKind>,
+    filenames: HashMap<&'static str, &'static FileKind>,
+    dirnames: HashMap<&'static str, &'static FileKind>,
+}
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| Registry {
+        extensions: EXTENSIONS.iter().copied().collect(),
+        filenames: FILENAMES.iter().copied().collect(),
+        dirnames: DIRNAMES.iter().copied().collect(),
+    })
+}
+
+/// lowercase extension of a file name, none for dotfiles like `.bashrc`
+pub fn extension_of(name: &str) -> Option<String> {
+    Path::new(name).extension().map(|e| e.to_string_lossy().to_lowercase())
+}
+
+/// what kind of file is this, going by the name only
+pub fn kind_for_name(name: &str) -> Option<&'static FileKind> {
+    let reg = registry();
+    if let Some(kind) = reg.filenames.get(name) {
+        return Some(kind);
+    }
+    extension_of(name).and_then(|ext| reg.extensions.get(ext.as_str()).copied())
+}
+
+// what --detect's content labels mean in registry terms. `binary` isn't in here, it says nothing
+static DETECTED: &[(&str, &FileKind)] = &[
+    ("elf", &EXECUTABLE), ("pe", &EXECUTABLE), ("mach-o", &EXECUTABLE), ("class", &OBJECT), ("wasm", &WASM),
+    ("png", &IMAGE), ("jpeg", &IMAGE), ("gif", &IMAGE), ("webp", &IMAGE), ("bmp", &IMAGE), ("ico", &IMAGE), ("tiff", &IMAGE),
+    ("pdf", &PDF), ("sqlite", &DATABASE),
+    ("zip", &ARCHIVE), ("gzip", &ARCHIVE), ("xz", &ARCHIVE), ("bzip2", &ARCHIVE), ("7z", &ARCHIVE), ("zstd", &ARCHIVE),
+    ("rar", &ARCHIVE), ("tar", &ARCHIVE),
+    ("mp3", &AUDIO), ("flac", &AUDIO), ("ogg", &AUDIO), ("wav", &AUDIO), ("m4a", &AUDIO), ("mp4", &VIDEO), ("mkv", &VIDEO),
+    ("ttf", &FONT), ("otf", &FONT), ("woff", &FONT), ("woff2", &FONT),
+    ("shell", &SHELL), ("script", &SHELL), ("python", &PYTHON), ("perl", &PERL), ("ruby", &RUBY), ("javascript", &JAVASCRIPT),
+    ("php", &PHP), ("lua", &LUA), ("powershell", &POWERSHELL),
+    ("text", &TEXT),
+];
+
+pub fn kind_for_detected(label: &str) -> Option<&'static FileKind> {
+    DETECTED.iter().find(|(l, _)| *l == label).map(|(_, k)| *k)
+}
+
+/// the name's kind, unless the content sniffed by --detect says it's something else entirely
+pub fn kind_for_file(name: &str, detected: Option<&str>) -> Option<&'static FileKind> {
+    let by_name = kind_for_name(name);
+    let Some(by_content) = detected.and_then(kind_for_detected) else {
+        return by_name;
+    };
+    match by_name {
+        // `text` only means it isn't binary, the name knows more than that
+        Some(kind) if std::ptr::eq(by_content, &TEXT) => Some(kind),
+        Some(kind) if kind.category == by_content.category => Some(kind),
+        // a shebang on a .rs or .py file is still that language
+        Some(kind) if matches!(kind.category, Code | Script) && matches!(by_content.category, Code | Script) => Some(kind),
+        // docx, jar, epub and apk are all zips inside
+        Some(kind) if by_content.category == Archive && matches!(kind.category, Document | Archive) => Some(kind),
+        _ => Some(by_content),
+    }
+}
+
+/// does a file fit `--type want`: a category like `image`, a kind like `python`, or a --detect label like `elf`
+pub fn type_matches(name: &str, detected: Option<&str>, want: &str) -> bool {
+    let want = want.to_lowercase();
+    if detected == Some(want.as_str()) {
+        return true;
+    }
+    kind_for_file(name, detected).is_some_and(|k| k.category.name() == want || k.name.to_lowercase() == want)
+}
+
+/// whether `--type` has any chance of matching something
+pub fn is_known_type(want: &str) -> bool {
+    let want = want.to_lowercase();
+    let reg = registry();
+    DETECTED.iter().any(|(label, _)| *label == want)
+        || reg.extensions.values().chain(reg.filenames.values()).any(|k| k.category.name() == want || k.name.to_lowercase() == want)
+}
+
+pub fn kind_for_dir(name: &str) -> &'static FileKind {
+    registry().dirnames.get(name).copied().unwrap_or(&DIR)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IconTheme {
+    None,
+    Text,
+    Nerd,
+    Emoji,
+}
+
+impl IconTheme {
+    pub fn parse(s: &str) -> Option<IconTheme> {
+        match s {
+            "none" => Some(IconTheme::None),
+            "text" => Some(IconTheme::Text),
+            "nerd" => Some(IconTheme::Nerd),
+            "emoji" => Some(IconTheme::Emoji),
+            _ => None,
+        }
+    }
+}
+
+pub struct IconSet {
+    theme: IconTheme,
+    overrides: IconOverrides,
+    big_file: u64,
+}
+
+impl IconSet {
+    pub fn new(theme: IconTheme, config: &IconConfig) -> IconSet {
+        let overrides = match theme {
+            IconTheme::None => IconOverrides::default(),
+            IconTheme::Text => config.text.clone(),
+            IconTheme::Nerd => config.nerd.clone(),
+            IconTheme::Emoji => config.emoji.clone(),
+        };
+        let mut overrides = overrides;
+        overrides.extensions = overrides.extensions.into_iter().map(|(k, v)| (k.trim_start_matches('.').to_lowercase(), v)).collect();
+        IconSet { theme, overrides, big_file: 1024 * 1024 }
+    }
+
+    pub fn set_big_file(&mut self, bytes: u64) {
+        self.big_file = bytes;
+    }
+
+    /// icon for an entry, with the trailing space baked in (empty for the none theme).
+    /// `detected` is the --detect label, when there is one it can overrule the extension
+    pub fn icon(&self, name: &str, is_dir: bool, size: Option<u64>, detected: Option<&str>) -> String {
+        if self.theme == IconTheme::None {
+            return String::new();
+        }
+        if let Some(custom) = self.overrides.filenames.get(name) {
+            return format!("{} ", custom);
+        }
+        let ext = extension_of(name);
+        if !is_dir {
+            if let Some(custom) = ext.as_ref().and_then(|e| self.overrides.extensions.get(e)) {
+                return format!("{} ", custom);
+            }
+        }
+        match self.theme {
+            IconTheme::Text => self.text_icon(name, ext.as_deref(), is_dir, size, detected),
+            IconTheme::Nerd | IconTheme::Emoji => {
+                let kind = if is_dir {
+                    kind_for_dir(name)
+                } else {
+                    kind_for_file(name, detected).unwrap_or(&TEXT_FALLBACK)
+                };
+                let glyph = if self.theme == IconTheme::Nerd { kind.nerd } else { kind.emoji };
+                format!("{} ", glyph)
+            }
+            IconTheme::None => String::new(),
+        }
+    }
+
+    // the old bracket tags, kept so scripts grepping for [DIR] don't break
+    fn text_icon(&self, name: &str, ext: Option<&str>, is_dir: bool, size: Option<u64>, detected: Option<&str>) -> String {
+        if is_dir {
+            return "[DIR] ".to_string();
+        }
+        if size.is_some_and(|s| s > self.big_file) {
+            return "[BIG] ".to_string();
+        }
+        let kind = match detected {
+            Some(_) => kind_for_file(name, detected),
+            None => ext.and_then(kind_for_ext),
+        };
+        if kind.is_some_and(|kind| [&EXECUTABLE, &BATCH, &SHELL].iter().any(|k| std::ptr::eq(*k, kind))) {
+            return "[EXEC] ".to_string();
+        }
+        // the sniffed label when the name has nothing to say or says the wrong thing, `[png]` for a renamed photo
+        let overruled = kind.is_some_and(|kind| kind_for_name(name).is_none_or(|k| !std::ptr::eq(k, kind)));
+        match (detected, ext) {
+            (Some(label), None) => format!("[{}] ", label),
+            (Some(label), Some(_)) if overruled => format!("[{}] ", label),
+            (_, Some(ext)) => format!("[{}] ", ext),
+            (None, None) => "[FILE] ".to_string(),
+        }
+    }
+}
+
+// plain file icon for anything the registry doesn't know
+static TEXT_FALLBACK: FileKind = kind("File", Text, "\u{f15b}", "📄");
+
+fn kind_for_ext(ext: &str) -> Option<&'static FileKind> {
+    registry().extensions.get(ext).copied()
+}
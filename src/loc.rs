@@ -0,0 +1,208 @@
+use crate::icons::{kind_for_name, FileKind};
+use crate::{collect_files, TreeNode};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+// --loc: lines of code, comments and blanks for the languages canopy knows by name
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Loc {
+    pub lines: u64,
+    pub code: u64,
+    pub comments: u64,
+    pub blank: u64,
+}
+
+impl Loc {
+    pub fn add(&mut self, other: &Loc) {
+        self.lines += other.lines;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blank += other.blank;
+    }
+}
+
+/// how a language writes its comments
+struct Syntax {
+    line: &'static [&'static str],
+    block: Option<(&'static str, &'static str)>,
+}
+
+const C_STYLE: Syntax = Syntax { line: &["//"], block: Some(("/*", "*/")) };
+const HASH: Syntax = Syntax { line: &["#"], block: None };
+const DASHES: Syntax = Syntax { line: &["--"], block: None };
+const SEMICOLON: Syntax = Syntax { line: &[";"], block: None };
+const PERCENT: Syntax = Syntax { line: &["%"], block: None };
+const SGML: Syntax = Syntax { line: &[], block: Some(("<!--", "-->")) };
+
+// keyed on the registry's kind names so a new extension for a language gets counted for free.
+// anything not in here (docs, data, images...) isn't code and gets no count
+fn syntax_for(kind: &FileKind) -> Option<&'static Syntax> {
+    let syntax = match kind.name {
+        "Rust" | "JavaScript" | "TypeScript" | "React" | "C" | "C++" | "C Header" | "C#" | "Go" | "Java" | "Kotlin"
+        | "Scala" | "Groovy" | "Swift" | "Objective-C" | "Dart" | "D" | "V" | "Solidity" | "Verilog" | "Protobuf"
+        | "Shader" | "Less" | "Sass" | "Gradle" => &C_STYLE,
+        "Zig" => &Syntax { line: &["//"], block: None },
+        "CSS" => &Syntax { line: &[], block: Some(("/*", "*/")) },
+        "PHP" | "Terraform" => &Syntax { line: &["//", "#"], block: Some(("/*", "*/")) },
+        "F#" => &Syntax { line: &["//"], block: Some(("(*", "*)")) },
+        "Pascal" => &Syntax { line: &["//"], block: Some(("{", "}")) },
+        "Python" | "Ruby" | "Perl" | "R" | "Elixir" | "Crystal" | "Shell" | "Makefile" | "Docker" | "CMake" | "YAML"
+        | "TOML" | "GraphQL" | "Env" | "Editor config" => &HASH,
+        "Julia" => &Syntax { line: &["#"], block: Some(("#=", "=#")) },
+        "Nim" => &Syntax { line: &["#"], block: Some(("#[", "]#")) },
+        "Nix" => &Syntax { line: &["#"], block: Some(("/*", "*/")) },
+        "PowerShell" => &Syntax { line: &["#"], block: Some(("<#", "#>")) },
+        "INI" => &Syntax { line: &[";", "#"], block: None },
+        "Lua" => &Syntax { line: &["--"], block: Some(("--[[", "]]")) },
+        "Haskell" | "Elm" | "PureScript" => &Syntax { line: &["--"], block: Some(("{-", "-}")) },
+        "SQL" => &Syntax { line: &["--"], block: Some(("/*", "*/")) },
+        "Ada" | "VHDL" => &DASHES,
+        "Clojure" | "Lisp" => &SEMICOLON,
+        "Assembly" => &Syntax { line: &[";", "#"], block: None },
+        "Erlang" | "TeX" => &PERCENT,
+        "OCaml" => &Syntax { line: &[], block: Some(("(*", "*)")) },
+        "HTML" | "XML" | "Vue" | "Svelte" | "Template" => &SGML,
+        "Batch" => &Syntax { line: &["::", "rem ", "REM ", "@rem ", "@REM "], block: None },
+        "Visual Basic" => &Syntax { line: &["'"], block: None },
+        "Fortran" => &Syntax { line: &["!"], block: None },
+        "COBOL" => &Syntax { line: &["*>"], block: None },
+        "Vim script" => &Syntax { line: &["\""], block: None },
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// line counts for one file, none when it isn't a known language or turns out to be binary
+pub fn count_file(path: &Path, name: &str) -> Option<Loc> {
+    let syntax = kind_for_name(name).and_then(syntax_for)?;
+    let mut file = File::open(path).ok()?;
+    // same check git and grep use: a NUL near the start means it's not text, whatever the name says
+    let mut head = Vec::with_capacity(8192);
+    file.by_ref().take(8192).read_to_end(&mut head).ok()?;
+    if head.contains(&0) {
+        return None;
+    }
+    // a line at a time, a multi-gigabyte .sql dump shouldn't have to fit in memory
+    count_lines(BufReader::new(head.as_slice().chain(file)), syntax).ok()
+}
+
+fn count_lines(mut reader: impl BufRead, syntax: &Syntax) -> io::Result<Loc> {
+    let mut loc = Loc::default();
+    // the end marker of the block comment we're inside, if any
+    let mut in_block: Option<&str> = None;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        loc.lines += 1;
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim();
+        if line.is_empty() {
+            loc.blank += 1;
+            continue;
+        }
+        if let Some(end) = in_block {
+            loc.comments += 1;
+            if let Some(i) = line.find(end) {
+                in_block = None;
+                // code after the comment closes still makes it a code line
+                let rest = line[i + end.len()..].trim();
+                if !rest.is_empty() && !is_comment_start(rest, syntax) {
+                    loc.comments -= 1;
+                    loc.code += 1;
+                }
+            }
+            continue;
+        }
+        if syntax.line.iter().any(|p| line.starts_with(p)) && !starts_block(line, syntax) {
+            loc.comments += 1;
+            continue;
+        }
+        if let Some((start, end)) = syntax.block.filter(|(start, _)| line.starts_with(start)) {
+            loc.comments += 1;
+            if !line[start.len()..].contains(end) {
+                in_block = Some(end);
+            }
+            continue;
+        }
+        loc.code += 1;
+        // `code(); /* starts a comment` leaves us inside it for the next lines
+        if let Some((start, end)) = syntax.block {
+            if let Some(i) = line.rfind(start) {
+                if !line[i + start.len()..].contains(end) {
+                    in_block = Some(end);
+                }
+            }
+        }
+    }
+    Ok(loc)
+}
+
+// lua's `--[[` starts with its line comment marker too, the block wins
+fn starts_block(line: &str, syntax: &Syntax) -> bool {
+    syntax.block.is_some_and(|(start, _)| line.starts_with(start))
+}
+
+fn is_comment_start(line: &str, syntax: &Syntax) -> bool {
+    syntax.line.iter().any(|p| line.starts_with(p)) || starts_block(line, syntax)
+}
+
+/// count every file in the tree, spread over all cores, then add the counts up into the folders
+pub fn fill_loc(tree: &mut TreeNode, root: &Path) {
+    let mut jobs = Vec::new();
+    collect_files(tree, root.to_path_buf(), &mut jobs);
+    // fifos would block the read forever, and a symlink's lines are already counted at its target
+    jobs.retain(|(node, _)| node.special.is_none());
+    jobs.into_par_iter().for_each(|(node, path)| {
+        node.loc = count_file(&path, &node.name);
+    });
+    roll_up(tree);
+}
+
+/// folders get the sum of everything below them, or nothing when there's no code in there at all
+fn roll_up(node: &mut TreeNode) -> Option<Loc> {
+    if !node.is_dir {
+        return node.loc;
+    }
+    let mut total: Option<Loc> = None;
+    for child in node.children.iter_mut() {
+        if let Some(loc) = roll_up(child) {
+            total.get_or_insert_with(Loc::default).add(&loc);
+        }
+    }
+    node.loc = total;
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(text: &str, syntax: &Syntax) -> Loc {
+        count_lines(text.as_bytes(), syntax).unwrap()
+    }
+
+    #[test]
+    fn counts_code_comments_and_blanks() {
+        let loc = count("// header\nfn main() {\n\n    /* one\n       two */\n    run(); // trailing\n}\n", &C_STYLE);
+        assert_eq!(loc, Loc { lines: 7, code: 3, comments: 3, blank: 1 });
+    }
+
+    #[test]
+    fn code_after_a_block_comment_closes_is_code() {
+        let loc = count("/* start\nend */ let x = 1;\nlet y = 2; /* open\nstill comment */\n", &C_STYLE);
+        assert_eq!(loc, Loc { lines: 4, code: 2, comments: 2, blank: 0 });
+    }
+
+    #[test]
+    fn crlf_and_missing_final_newline() {
+        let loc = count("# comment\r\nx = 1\r\n\r\ny = 2", &HASH);
+        assert_eq!(loc, Loc { lines: 4, code: 2, comments: 1, blank: 1 });
+    }
+}
@@ -0,0 +1,170 @@
+use crate::meta::{format_mode, format_time_iso, format_time_relative};
+use crate::{SizeStyle, TreeNode};
+use colored::Colorize;
+use unicode_width::UnicodeWidthChar;
+
+// the -l layout: ls -l style columns on the left, the tree on the right
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Column {
+    Perms,
+    Owner,
+    Group,
+    Size,
+    Mtime,
+    Loc,
+    Git,
+}
+
+impl Column {
+    pub fn parse(s: &str) -> Option<Column> {
+        match s {
+            "perms" | "permissions" | "mode" => Some(Column::Perms),
+            "owner" | "user" => Some(Column::Owner),
+            "group" => Some(Column::Group),
+            "size" => Some(Column::Size),
+            "mtime" | "time" | "modified" => Some(Column::Mtime),
+            "loc" | "lines" => Some(Column::Loc),
+            "git" => Some(Column::Git),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_COLUMNS: [Column; 5] = [Column::Perms, Column::Owner, Column::Group, Column::Size, Column::Mtime];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeStyle {
+    Iso,
+    Relative,
+}
+
+pub struct LongLayout<'a> {
+    columns: Vec<Column>,
+    widths: Vec<usize>,
+    time_style: TimeStyle,
+    sizes: &'a SizeStyle,
+    now: i64,
+    term_width: Option<usize>,
+}
+
+impl<'a> LongLayout<'a> {
+    pub fn new(columns: Vec<Column>, time_style: TimeStyle, sizes: &'a SizeStyle, tree: &TreeNode) -> LongLayout<'a> {
+        let now = chrono::Local::now().timestamp();
+        let mut layout = LongLayout {
+            widths: vec![0; columns.len()],
+            columns,
+            time_style,
+            sizes,
+            now,
+            term_width: terminal_width(),
+        };
+        layout.measure(tree);
+        layout
+    }
+
+    fn measure(&mut self, node: &TreeNode) {
+        for i in 0..self.columns.len() {
+            let w = self.cell(self.columns[i], node).chars().count();
+            self.widths[i] = self.widths[i].max(w);
+        }
+        for child in &node.children {
+            self.measure(child);
+        }
+    }
+
+    fn cell(&self, column: Column, node: &TreeNode) -> String {
+        match column {
+            Column::Perms => node.mode.map(format_mode).unwrap_or_else(|| "-".to_string()),
+            Column::Owner => node.owner.clone().unwrap_or_else(|| "-".to_string()),
+            Column::Group => node.group.clone().unwrap_or_else(|| "-".to_string()),
+            Column::Size => match node.size {
+                Some(s) => self.sizes.format(s),
+                None => "-".to_string(),
+            },
+            Column::Mtime => match node.mtime {
+                Some(t) if self.time_style == TimeStyle::Relative => format_time_relative(t, self.now),
+                Some(t) => format_time_iso(t),
+                None => "-".to_string(),
+            },
+            // code lines only, the exports have the comment and blank counts
+            Column::Loc => node.loc.map(|l| l.code.to_string()).unwrap_or_else(|| "-".to_string()),
+            Column::Git => node.git.map_or(" ", |s| s.marker()).to_string(),
+        }
+    }
+
+    /// total width of the column block, separators included
+    pub fn width(&self) -> usize {
+        self.widths.iter().map(|w| w + 1).sum::<usize>() + 1
+    }
+
+    /// padded, colored columns for one node
+    pub fn cells(&self, node: &TreeNode) -> String {
+        let mut out = String::new();
+        for (column, width) in self.columns.iter().zip(&self.widths) {
+            let text = self.cell(*column, node);
+            let cell = match column {
+                // numbers and times line up on the right like ls does
+                Column::Size | Column::Mtime | Column::Loc => format!("{:>width$}", text, width = *width),
+                _ => format!("{:<width$}", text, width = *width),
+            };
+            let cell = match (column, node.size) {
+                (Column::Size, Some(s)) => cell.color(self.sizes.color(s)).to_string(),
+                (Column::Perms, _) => cell.dimmed().to_string(),
+                (Column::Git, _) => match node.git {
+                    Some(state) => cell.color(state.color()).to_string(),
+                    None => cell,
+                },
+                _ => cell,
+            };
+            out.push_str(&cell);
+            out.push(' ');
+        }
+        out.push(' ');
+        out
+    }
+
+    /// spaces as wide as the columns, for lines that aren't nodes
+    pub fn blank(&self) -> String {
+        " ".repeat(self.width())
+    }
+
+    pub fn term_width(&self) -> Option<usize> {
+        self.term_width
+    }
+}
+
+fn terminal_width() -> Option<usize> {
+    use std::io::IsTerminal;
+    if !std::io::stdout().is_terminal() {
+        return std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok());
+    }
+    crossterm::terminal::size().ok().map(|(w, _)| w as usize)
+}
+
+/// cut a string down to `max` terminal cells, ending with … when it had to cut
+pub fn truncate_to_width(s: &str, max: usize) -> String {
+    let total: usize = s.chars().map(|c| c.width().unwrap_or(0)).sum();
+    if total <= max {
+        return s.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > max {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
@@ -1,5 +1,33 @@
-use clap::Parser;
+mod archive;
+mod config;
+mod detect;
+mod diff;
+mod dupes;
+mod formats;
+mod git;
+mod graph;
+mod hash;
+mod html;
+mod icons;
+mod loc;
+mod long;
+mod manifest;
+mod markdown;
+mod meta;
+mod notes;
+mod rules;
+mod scaffold;
+mod stats;
+mod stream;
+mod svg;
+mod table;
+mod top;
+
+use clap::{Parser, Subcommand};
 use colored::{Color, Colorize};
+use icons::{IconSet, IconTheme};
+use long::{Column, LongLayout, TimeStyle};
+use table::{CsvColumn, CsvOptions};
 use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind};
 use ratatui::{
     backend::CrosstermBackend,
@@ -9,25 +37,166 @@ use ratatui::{
     Terminal,
 };
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io;
-use std::io::Write;
-use std::path::Path;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 struct TreeNode {
     name: String,
     is_dir: bool,
     size: Option<u64>,
+    // metadata below is only filled in when asked for (-l), so old json stays the same
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime: Option<i64>,
+    // `sha256:...` content digest from --hash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    // comment from the --annotations sidecar
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    // line counts from --loc, folders have their contents added up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    loc: Option<loc::Loc>,
+    // what --detect made of the first bytes, `png`, `elf`, `text`...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detected: Option<String>,
+    // --git state, folders have the worst of what's inside them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    git: Option<git::GitState>,
+    // why the scan couldn't list this folder (or read this entry), the rest of the tree still gets built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<ScanError>,
+    // symlinks, fifos, sockets and devices, only the csv type column looks at this
+    #[serde(skip)]
+    special: Option<Special>,
     children: Vec<TreeNode>,
 }
 
+/// everything under a node, folders don't carry a size of their own
+fn total_size(node: &TreeNode) -> u64 {
+    node.size.unwrap_or(0) + node.children.iter().map(total_size).sum::<u64>()
+}
+
+/// `parent/name`, or just `name` at the root
+fn join_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// every file below a node with its path on disk, for the passes that fill in one field per file
+fn collect_files<'a>(node: &'a mut TreeNode, path: PathBuf, jobs: &mut Vec<(&'a mut TreeNode, PathBuf)>) {
+    if !node.is_dir {
+        jobs.push((node, path));
+        return;
+    }
+    for child in node.children.iter_mut() {
+        let child_path = path.join(&child.name);
+        collect_files(child, child_path, jobs);
+    }
+}
+
+/// for text and attributes in html, svg and xml
+fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // control chars aren't allowed in xml 1.0 at all, not even escaped
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => out.push('\u{fffd}'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct ScanError {
+    kind: ScanErrorKind,
+    message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum ScanErrorKind {
+    PermissionDenied,
+    /// deleted between being listed and being read, build folders and temp files do this
+    Vanished,
+    Io,
+}
+
+impl ScanError {
+    fn from_io(e: &io::Error) -> ScanError {
+        let kind = match e.kind() {
+            io::ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound => ScanErrorKind::Vanished,
+            _ => ScanErrorKind::Io,
+        };
+        ScanError { kind, message: e.to_string() }
+    }
+
+    /// short enough to go next to a name in the tree
+    fn label(&self) -> &'static str {
+        match self.kind {
+            ScanErrorKind::PermissionDenied => "permission denied",
+            ScanErrorKind::Vanished => "vanished during scan",
+            ScanErrorKind::Io => "read error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Special {
+    Symlink,
+    Fifo,
+    Socket,
+    Device,
+    Other,
+}
+
+impl Special {
+    fn name(self) -> &'static str {
+        match self {
+            Special::Symlink => "symlink",
+            Special::Fifo => "fifo",
+            Special::Socket => "socket",
+            Special::Device => "device",
+            Special::Other => "other",
+        }
+    }
+
+    /// fifos, sockets and devices: opening them blocks or never ends, so nothing should read them
+    fn is_stream(self) -> bool {
+        self != Special::Symlink
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "virex-canopy")]
 #[command(about = "Generate and visualize directory tree structures")]
 #[command(disable_help_flag = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// === path to the dir to visualize === ///
     path: Option<String>,
 
@@ -44,7 +213,7 @@ struct Args {
     #[arg(long)]
     collapse: bool,
 
-    /// === export to json or csv, for programmatic use === ///
+    /// === export to json, ndjson, csv, tsv, yaml, toml, xml, html, dot, mermaid, svg-treemap or svg-sunburst === ///
     #[arg(long)]
     export: Option<String>,
 
@@ -67,6 +236,341 @@ struct Args {
     /// === check if exe is in PATH === ///
     #[arg(long)]
     check_path: bool,
+
+    /// === when to use colors: auto, always or never === ///
+    #[arg(long, default_value = "auto", global = true)]
+    color: String,
+
+    /// === icon theme: none, text, nerd or emoji === ///
+    #[arg(long)]
+    icons: Option<String>,
+
+    /// === long listing, ls -l style columns next to the tree === ///
+    #[arg(short = 'l', long)]
+    long: bool,
+
+    /// === which columns to show, e.g. perms,size,mtime for -l or path,depth,size for csv === ///
+    #[arg(long)]
+    columns: Option<String>,
+
+    /// === show exact byte counts instead of 1.2K style sizes === ///
+    #[arg(long)]
+    bytes: bool,
+
+    /// === use powers of 1000 (kB, MB) instead of 1024 === ///
+    #[arg(long)]
+    si: bool,
+
+    /// === where size colors change, e.g. 10K,10M (green below, red above) === ///
+    #[arg(long)]
+    size_colors: Option<String>,
+
+    /// === files above this get the [BIG] tag, e.g. 100M === ///
+    #[arg(long)]
+    big_file: Option<String>,
+
+    /// === scale size colors to the largest file in the tree === ///
+    #[arg(long)]
+    relative_sizes: bool,
+
+    /// === only draw this many levels in dot/mermaid exports === ///
+    #[arg(long)]
+    graph_depth: Option<usize>,
+
+    /// === put sizes on dot/mermaid nodes === ///
+    #[arg(long)]
+    graph_sizes: bool,
+
+    /// === group dot/mermaid nodes by top level folder === ///
+    #[arg(long)]
+    graph_cluster: bool,
+
+    /// === color svg exports by ext (extension) or depth === ///
+    #[arg(long, default_value = "ext")]
+    svg_color: String,
+
+    /// === in svg exports, lump files smaller than this many square pixels together === ///
+    #[arg(long, default_value_t = 24.0)]
+    svg_min_area: f64,
+
+    /// === output style: tree, plain or markdown === ///
+    #[arg(long, default_value = "tree")]
+    format: String,
+
+    /// === markdown as a fenced code block (fence) or a bullet list (list) === ///
+    #[arg(long, default_value = "fence")]
+    md_style: String,
+
+    /// === link files in markdown lists, optionally under a base url === ///
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    md_links: Option<String>,
+
+    /// === sidecar file with per-entry comments, lines like `src/  # core library` === ///
+    #[arg(long)]
+    annotations: Option<String>,
+
+    /// === field separator for csv exports, e.g. ; or tab === ///
+    #[arg(long)]
+    delimiter: Option<String>,
+
+    /// === leave the header row out of csv/tsv exports === ///
+    #[arg(long)]
+    no_header: bool,
+
+    /// === hash every file: sha256, blake3 or xxh3 === ///
+    #[arg(long)]
+    hash: Option<String>,
+
+    /// === don't hash files bigger than this, e.g. 1G === ///
+    #[arg(long)]
+    hash_max_size: Option<String>,
+
+    /// === read the tree from an earlier --json export instead of the disk, - for stdin === ///
+    #[arg(long)]
+    from_json: Option<String>,
+
+    /// === how to show times in the long listing: iso or relative === ///
+    #[arg(long, default_value = "iso")]
+    time_style: String,
+
+    /// === sniff the first bytes of every file to tell what it is, not just its extension === ///
+    #[arg(long)]
+    detect: bool,
+
+    /// === only files of these types, e.g. image or code,config or elf === ///
+    #[arg(long = "type")]
+    file_type: Option<String>,
+
+    /// === also list what's inside zip and tar archives found while walking === ///
+    #[arg(long)]
+    archives: bool,
+
+    /// === show the tree a git revision (branch, tag, commit) had, without checking it out === ///
+    #[arg(long)]
+    git_rev: Option<String>,
+
+    /// === mark entries modified, staged, untracked, ignored or conflicted in their git repository === ///
+    #[arg(long)]
+    git: bool,
+
+    /// === count lines of code, comments and blanks, rolled up per folder === ///
+    #[arg(long)]
+    loc: bool,
+
+    /// === breakdown by extension, type and depth after the tree, or on its own as json or csv === ///
+    #[arg(long, num_args = 0..=1, default_missing_value = "table")]
+    stats: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// === what changed between two folders or --json snapshots === ///
+    Diff(DiffArgs),
+
+    /// === find duplicate files by size, then partial hash, then full hash === ///
+    Dupes(DupesArgs),
+
+    /// === the biggest files and folders, the fullest folders, the newest and oldest files === ///
+    Top(TopArgs),
+
+    /// === checksum manifests: create one for a folder, verify a folder against one === ///
+    Manifest {
+        #[command(subcommand)]
+        action: ManifestAction,
+    },
+
+    /// === create the folders and files a drawn tree, markdown list or --json tree describes === ///
+    Scaffold(ScaffoldArgs),
+
+    /// === check a folder against layout rules: required, forbidden, max size and max depth === ///
+    Check(CheckArgs),
+}
+
+#[derive(clap::Args)]
+struct CheckArgs {
+    /// === folder to check === ///
+    #[arg(default_value = ".")]
+    path: String,
+
+    /// === the rules, a toml file === ///
+    #[arg(long, default_value = "canopy-rules.toml")]
+    rules: String,
+
+    /// === text, json or sarif === ///
+    #[arg(long, default_value = "text")]
+    format: String,
+
+    /// === include hidden files starting with . === ///
+    #[arg(long)]
+    hidden: bool,
+}
+
+#[derive(clap::Args)]
+struct ScaffoldArgs {
+    /// === the tree to create, canopy output, a markdown list or a --json tree (- for stdin) === ///
+    spec: String,
+
+    /// === folder to create it in === ///
+    #[arg(long, default_value = ".")]
+    into: String,
+
+    /// === only show what would be created === ///
+    #[arg(long)]
+    dry_run: bool,
+
+    /// === write over files that already exist === ///
+    #[arg(long)]
+    force: bool,
+
+    /// === fill files from the template with the same path or name in this folder === ///
+    #[arg(long)]
+    templates: Option<String>,
+
+    /// === KEY=VALUE for {{KEY}} in templates, can be given more than once === ///
+    #[arg(long = "var")]
+    vars: Vec<String>,
+}
+
+#[derive(clap::Args)]
+struct DupesArgs {
+    /// === folder to search === ///
+    path: String,
+
+    /// === print the groups as json === ///
+    #[arg(long)]
+    json: bool,
+
+    /// === show the duplicates as a tree instead of a list of groups === ///
+    #[arg(long)]
+    tree: bool,
+
+    /// === mark duplicates in a list and move them to the trash === ///
+    #[arg(long)]
+    interactive: bool,
+
+    /// === ignore files smaller than this, e.g. 1M === ///
+    #[arg(long)]
+    min_size: Option<String>,
+
+    /// === count hard links to the same file once === ///
+    #[arg(long)]
+    merge_hardlinks: bool,
+
+    /// === hash for the final comparison: sha256, blake3 or xxh3 === ///
+    #[arg(long, default_value = "blake3")]
+    algo: String,
+
+    /// === include hidden files starting with . === ///
+    #[arg(long)]
+    hidden: bool,
+}
+
+#[derive(clap::Args)]
+struct TopArgs {
+    /// === folder to scan === ///
+    path: String,
+
+    /// === how many entries per list === ///
+    #[arg(short = 'n', default_value_t = 20)]
+    n: usize,
+
+    /// === size (files and folders), count (entries per folder) or mtime (newest and oldest) === ///
+    #[arg(long, default_value = "size")]
+    by: String,
+
+    /// === print the lists as json === ///
+    #[arg(long)]
+    json: bool,
+
+    /// === include hidden files starting with . === ///
+    #[arg(long)]
+    hidden: bool,
+}
+
+#[derive(Subcommand)]
+enum ManifestAction {
+    /// === hash every file and write the manifest === ///
+    Create(ManifestCreateArgs),
+
+    /// === report missing, extra and corrupted files === ///
+    Verify(ManifestVerifyArgs),
+}
+
+#[derive(clap::Args)]
+struct ManifestCreateArgs {
+    /// === folder to hash === ///
+    path: String,
+
+    /// === sha256, blake3 or xxh3 === ///
+    #[arg(long, default_value = "sha256")]
+    algo: String,
+
+    /// === sums (sha256sum compatible) or json === ///
+    #[arg(long, default_value = "sums")]
+    format: String,
+
+    /// === write here instead of stdout === ///
+    #[arg(short = 'o', long)]
+    output: Option<String>,
+
+    /// === include hidden files starting with . === ///
+    #[arg(long)]
+    hidden: bool,
+
+    /// === leave out files bigger than this, e.g. 1G === ///
+    #[arg(long)]
+    hash_max_size: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct ManifestVerifyArgs {
+    /// === the manifest, sums or json === ///
+    manifest: String,
+
+    /// === folder the paths are relative to, defaults to the manifest's folder === ///
+    #[arg(long)]
+    root: Option<String>,
+
+    /// === algorithm for sums files, json manifests say it themselves. without it 16 hex digits means xxh3, else sha256 === ///
+    #[arg(long)]
+    algo: Option<String>,
+
+    /// === include hidden files starting with . === ///
+    #[arg(long)]
+    hidden: bool,
+
+    /// === don't check files bigger than this, e.g. 1G === ///
+    #[arg(long)]
+    hash_max_size: Option<String>,
+
+    /// === files not in the manifest are fine === ///
+    #[arg(long)]
+    ignore_extra: bool,
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    /// === the old side, a folder, a --json snapshot or a git revision === ///
+    before: String,
+
+    /// === the new side, a folder, a --json snapshot or a git revision === ///
+    after: String,
+
+    /// === compare file contents, not just size and mtime === ///
+    #[arg(long)]
+    hash: bool,
+
+    /// === show unchanged entries too === ///
+    #[arg(long)]
+    all: bool,
+
+    /// === print the changes as json, for ci checks === ///
+    #[arg(long)]
+    json: bool,
+
+    /// === include hidden files starting with . === ///
+    #[arg(long)]
+    hidden: bool,
 }
 
 fn print_welcome() {
@@ -81,12 +585,83 @@ fn print_welcome() {
     println!("  [x] --depth <N>               Limit tree depth");
     println!("  [x] --hidden                  Include hidden files");
     println!("  [x] --json                    Export tree as JSON");
+    println!("  [x] --export <FORMAT>         Export as json, ndjson, csv, tsv, yaml, toml, xml, html, dot,");
+    println!("                                mermaid, svg-treemap or svg-sunburst");
+    println!("  [x] --delimiter <CHAR>        Field separator for csv exports, e.g. ; or tab");
+    println!("  [x] --no-header               Leave the header row out of csv/tsv exports");
+    println!("  [x] --svg-color <MODE>        Color svg-treemap/svg-sunburst by ext or depth");
+    println!("  [x] --svg-min-area <PX>       Group files smaller than this many square pixels");
+    println!("  [x] --graph-depth <N>         Only draw N levels in dot/mermaid exports");
+    println!("  [x] --graph-sizes             Put sizes on dot/mermaid nodes");
+    println!("  [x] --graph-cluster           Group dot/mermaid nodes by top level folder");
+    println!("  [x] --hash <ALGO>             Hash every file: sha256, blake3 or xxh3");
+    println!("  [x] --hash-max-size <SIZE>    Skip hashing files bigger than this, e.g. 1G");
+    println!("  [x] --from-json <FILE>        Render or re-export a tree saved with --json (- for stdin)");
+    println!("  [x] --detect                  Tell file types apart by their contents, not just the extension");
+    println!("  [x] --type <LIST>             Only show files of these types, e.g. image, code,config or elf");
+    println!("  [x] --git                     Mark entries modified (M), staged (S), untracked (?), ignored (!)");
+    println!("                                or conflicted (U) in their git repository");
+    println!("  [x] --git-rev <REV>           Show the tree of a branch, tag or commit without checking it out");
+    println!("  [x] --archives                List the contents of zip and tar archives inside the tree too");
+    println!("  [x] --loc                     Count code, comment and blank lines per file and folder");
+    println!("  [x] --stats [FORMAT]          Counts and bytes per extension, type and depth: table, json or csv");
     println!("  [x] --help                    Show this message");
     println!("  [x] --interactive             Enable interactive TUI mode");
     println!("  [x] --filter <PATTERN>        Filter files with glob pattern, e.g. *.rs");
-    println!("  [x] --test-colors             Test color output");
+    println!("  [x] -l, --long                Show permissions, owner, group, size and mtime columns");
+    println!("  [x] --columns <LIST>          Pick long listing columns: perms,owner,group,size,mtime,loc,git");
+    println!("                                or csv columns: name,path,abspath,parent,depth,ext,type,");
+    println!("                                size,mtime,mode,owner,group,hash,lines,code,comments,blank,");
+    println!("                                detected,git");
+    println!("  [x] --bytes                   Show exact byte counts");
+    println!("  [x] --si                      Use powers of 1000 for sizes (kB, MB, GB)");
+    println!("  [x] --size-colors <A,B>       Size color thresholds, e.g. 10K,10M");
+    println!("  [x] --big-file <SIZE>         Size above which files are tagged [BIG]");
+    println!("  [x] --relative-sizes          Scale size colors to the largest file");
+    println!("  [x] --time-style <STYLE>      Long listing times: iso or relative");
+    println!("  [x] --format <FORMAT>         Output style: tree, plain or markdown");
+    println!("  [x] --md-style <STYLE>        Markdown as a fenced block (fence) or bullet list (list)");
+    println!("  [x] --md-links [BASE]         Link entries in markdown lists, optionally under a base url");
+    println!("  [x] --annotations <FILE>      Add comments to entries from a sidecar file");
+    println!("  [x] --icons <THEME>           Icon theme: none, text, nerd or emoji");
+    println!("  [x] --color <WHEN>            Use colors: auto, always or never");
+    println!("  [x] --test-colors             Test color output and show what the terminal supports");
     println!("  [x] --check-path              Check if exe is in PATH");
     println!();
+    println!("Subcommands:");
+    println!("  [x] diff <A> <B>              What changed between two folders, --json snapshots or git revisions");
+    println!("        --hash                  Compare file contents, not just size and mtime");
+    println!("        --all                   Show unchanged entries too");
+    println!("        --json                  Print the changes as json for ci checks");
+    println!("  [x] dupes <PATH>              Find duplicate files and how much space they waste");
+    println!("        --tree                  Show them as a tree instead of a list of groups");
+    println!("        --json                  Print the groups as json");
+    println!("        --interactive           Mark duplicates and move them to the trash");
+    println!("        --min-size <SIZE>       Ignore files smaller than this");
+    println!("        --merge-hardlinks       Count hard links to the same file once");
+    println!("  [x] top <PATH>                Biggest files and folders, fullest folders, newest/oldest files");
+    println!("        -n <N>                  How many per list (default 20)");
+    println!("        --by <WHAT>             size, count or mtime");
+    println!("        --json                  Print the lists as json");
+    println!("  [x] manifest create <PATH>    Write a checksum manifest for a folder");
+    println!("        --algo <ALGO>           sha256, blake3 or xxh3");
+    println!("        --format <FORMAT>       sums (sha256sum compatible) or json");
+    println!("        -o, --output <FILE>     Write to a file instead of stdout");
+    println!("  [x] manifest verify <FILE>    Report missing, extra and corrupted files");
+    println!("        --root <DIR>            Folder the paths are relative to");
+    println!("        --ignore-extra          Don't fail on files missing from the manifest");
+    println!("  [x] scaffold <SPEC>           Create the folders and files a tree drawing, markdown list or --json describes");
+    println!("        --into <DIR>            Folder to create them in (default .)");
+    println!("        --dry-run               Only show what would be created");
+    println!("        --force                 Write over files that already exist");
+    println!("        --templates <DIR>       Fill files from templates with the same path or name");
+    println!("        --var <KEY=VALUE>       Value for {{{{KEY}}}} in templates");
+    println!("  [x] check [PATH]              Check the layout against rules, exits 1 on errors for ci, 2 if it couldn't read everything");
+    println!("        --rules <FILE>          Rules file (default canopy-rules.toml)");
+    println!("        --format <FORMAT>       text, json or sarif");
+    println!();
+    println!("Exit codes: 0 complete, 1 error, 2 partial scan (some folders or files, the config or git status couldn't be read)");
+    println!();
     println!("Tip: Try `virex-canopy . --depth 2` to explore your current folder!");
 }
 
@@ -118,32 +693,175 @@ fn enable_ansi() {
     }
 }
 
-fn format_size(bytes: u64) -> String {
+fn format_size(bytes: u64, si: bool) -> String {
     // format bytes to human readable, damn big numbers
-    if bytes < 1024 {
-        format!("{}B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.1}K", bytes as f64 / 1024.0)
-    } else if bytes < 1024 * 1024 * 1024 {
-        format!("{:.1}M", bytes as f64 / (1024.0 * 1024.0))
+    // --si means powers of 1000 like disk vendors, otherwise the usual 1024
+    // and then the units say so too, `kB` and `MB` read back as 1000 based by parse_size
+    let (base, units) = if si {
+        (1000.0, ["kB", "MB", "GB", "TB", "PB", "EB"])
     } else {
-        format!("{:.1}G", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+        (1024.0, ["K", "M", "G", "T", "P", "E"])
+    };
+    if (bytes as f64) < base {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64 / base;
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
     }
+    format!("{:.1}{}", value, units[unit])
 }
 
-fn get_size_color(size: u64) -> Color {
-    if size < 1024 {
+/// parse sizes like `512`, `10K`, `1.5M`, `2GiB` (1024 based) or `10KB`, `1MB` (1000 based)
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (num, suffix) = s.split_at(split);
+    let num: f64 = num.parse().ok()?;
+    let suffix = suffix.trim();
+    let (letter, rest) = match suffix.chars().next() {
+        // `µ` and friends are more than one byte, slicing at 1 would panic
+        Some(c) => (c.to_ascii_uppercase(), &suffix[c.len_utf8()..]),
+        None => return Some(num as u64),
+    };
+    let base: f64 = match rest {
+        "" | "iB" | "ib" => 1024.0,
+        "B" | "b" => 1000.0,
+        _ => return None,
+    };
+    let power = match letter {
+        'B' if rest.is_empty() => 0,
+        'K' => 1,
+        'M' => 2,
+        'G' => 3,
+        'T' => 4,
+        'P' => 5,
+        'E' => 6,
+        _ => return None,
+    };
+    Some((num * base.powi(power)) as u64)
+}
+
+/// everything about how sizes look: units, and where green turns yellow turns red
+struct SizeStyle {
+    si: bool,
+    exact: bool,
+    medium: u64,
+    large: u64,
+    big_file: u64,
+}
+
+impl SizeStyle {
+    fn format(&self, bytes: u64) -> String {
+        if self.exact {
+            bytes.to_string()
+        } else {
+            format_size(bytes, self.si)
+        }
+    }
+
+    fn color(&self, bytes: u64) -> Color {
+        get_size_color(bytes, self.medium, self.large)
+    }
+
+    // thresholds follow the biggest file instead of fixed numbers, handy for trees
+    // where everything is huge (or everything is tiny)
+    fn scale_to(&mut self, largest: u64) {
+        self.medium = (largest / 100).max(1);
+        self.large = (largest / 10).max(1);
+        self.big_file = (largest / 2).max(1);
+    }
+}
+
+fn get_size_color(size: u64, medium: u64, large: u64) -> Color {
+    if size < medium {
         Color::Green
-    } else if size < 1024 * 1024 {
+    } else if size < large {
         Color::Yellow
     } else {
         Color::Red
     }
 }
 
-fn main() {
-    enable_ansi();
+fn largest_file(node: &TreeNode) -> u64 {
+    let own = node.size.unwrap_or(0);
+    node.children.iter().map(largest_file).fold(own, u64::max)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ColorSupport {
+    None,
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    fn describe(self) -> &'static str {
+        match self {
+            ColorSupport::None => "none",
+            ColorSupport::Basic => "16 colors",
+            ColorSupport::Ansi256 => "256 colors",
+            ColorSupport::TrueColor => "truecolor (24-bit)",
+        }
+    }
+}
+
+fn env_flag(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+fn detect_color_support() -> ColorSupport {
+    // guess what the terminal can do from the usual env vars, no terminfo lookups
+    let term = env_flag("TERM").unwrap_or_default().to_lowercase();
+    if term == "dumb" {
+        return ColorSupport::None;
+    }
+    let colorterm = env_flag("COLORTERM").unwrap_or_default().to_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" || term.contains("direct") {
+        return ColorSupport::TrueColor;
+    }
+    // windows terminal and the big mac/linux emulators do 24-bit without saying so
+    if env_flag("WT_SESSION").is_some() {
+        return ColorSupport::TrueColor;
+    }
+    if let Some(program) = env_flag("TERM_PROGRAM") {
+        if matches!(program.as_str(), "iTerm.app" | "WezTerm" | "vscode" | "ghostty") {
+            return ColorSupport::TrueColor;
+        }
+    }
+    if term.contains("256color") {
+        return ColorSupport::Ansi256;
+    }
+    if term.is_empty() && !cfg!(windows) {
+        return ColorSupport::None;
+    }
+    ColorSupport::Basic
+}
 
+fn should_use_color(mode: &str, exporting: bool) -> bool {
+    match mode {
+        "always" => true,
+        "never" => false,
+        _ => {
+            // exports are for programs, never put escapes in there
+            if exporting || env_flag("NO_COLOR").is_some() {
+                return false;
+            }
+            if env_flag("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+                return true;
+            }
+            if env_flag("CLICOLOR").is_some_and(|v| v == "0") {
+                return false;
+            }
+            io::stdout().is_terminal() && detect_color_support() != ColorSupport::None
+        }
+    }
+}
+
+fn main() {
     if std::env::args().any(|arg| arg == "--help" || arg == "-h") {
         print_welcome();
         return;
@@ -169,150 +887,1101 @@ fn main() {
         }
     }
 
-    let args = Args::parse();
+    let args = Args::parse();
+
+    let export_format = if args.json { Some("json") } else { args.export.as_deref() };
+
+    if !matches!(args.color.as_str(), "auto" | "always" | "never") {
+        eprintln!("Invalid color mode: {} (expected auto, always or never)", args.color);
+        std::process::exit(1);
+    }
+    if !matches!(args.format.as_str(), "tree" | "plain" | "markdown") {
+        eprintln!("Invalid format: {} (expected tree, plain or markdown)", args.format);
+        std::process::exit(1);
+    }
+    if !matches!(args.md_style.as_str(), "fence" | "list") {
+        eprintln!("Invalid markdown style: {} (expected fence or list)", args.md_style);
+        std::process::exit(1);
+    }
+    match (args.stats.as_deref(), export_format) {
+        (None, _) | (Some("table" | "json" | "csv"), None) => {}
+        (Some("table" | "json" | "csv"), Some(_)) => {
+            eprintln!("Error: --stats can't be combined with --export, use --stats json or --stats csv on their own");
+            std::process::exit(1);
+        }
+        (Some(other), _) => {
+            eprintln!("Invalid stats format: {} (expected table, json or csv)", other);
+            std::process::exit(1);
+        }
+    }
+    // plain and markdown end up in docs, treat them like exports: no escapes, no icons
+    let doc_format = args.format != "tree";
+    let use_color = should_use_color(&args.color, export_format.is_some() || doc_format);
+    colored::control::set_override(use_color);
+    if use_color {
+        enable_ansi();
+    }
+
+    if args.check_path {
+        let exe_path = std::env::current_exe().unwrap();
+        let exe_name = exe_path.file_name().unwrap().to_string_lossy();
+        let path_var = std::env::var_os("PATH").unwrap_or_default();
+        let paths: Vec<std::path::PathBuf> = std::env::split_paths(&path_var).collect();
+        let found_path = paths.iter().find(|p| p.join(exe_name.as_ref()).exists());
+        if let Some(p) = found_path {
+            println!("yes {}", p.join(exe_name.as_ref()).display());
+        } else {
+            println!("no");
+        }
+        return;
+    }
+
+    let config = config::load_config();
+    let theme_name = args.icons.as_deref().or(config.icons.theme.as_deref()).unwrap_or("text");
+    let theme = match IconTheme::parse(theme_name) {
+        Some(t) => t,
+        None => {
+            eprintln!("Invalid icon theme: {} (expected none, text, nerd or emoji)", theme_name);
+            std::process::exit(1);
+        }
+    };
+    let mut icons = IconSet::new(if doc_format { IconTheme::None } else { theme }, &config.icons);
+
+    let size_setting = |flag: Option<&str>, cfg: Option<&str>, default: u64, what: &str| -> u64 {
+        match flag.or(cfg) {
+            Some(text) => parse_size(text).unwrap_or_else(|| {
+                eprintln!("Invalid size for {}: {}", what, text);
+                std::process::exit(1);
+            }),
+            None => default,
+        }
+    };
+    let (medium_flag, large_flag) = match args.size_colors.as_deref() {
+        Some(pair) => match pair.split_once(',') {
+            Some((a, b)) => (Some(a), Some(b)),
+            None => {
+                eprintln!("Invalid --size-colors: {} (expected two sizes like 10K,10M)", pair);
+                std::process::exit(1);
+            }
+        },
+        None => (None, None),
+    };
+    let mut sizes = SizeStyle {
+        si: args.si || config.sizes.si,
+        exact: args.bytes,
+        medium: size_setting(medium_flag, config.sizes.medium.as_deref(), 1024, "medium size color"),
+        large: size_setting(large_flag, config.sizes.large.as_deref(), 1024 * 1024, "large size color"),
+        big_file: size_setting(args.big_file.as_deref(), config.sizes.big_file.as_deref(), 1024 * 1024, "--big-file"),
+    };
+    if let (Some(pair), true) = (args.size_colors.as_deref(), sizes.medium >= sizes.large) {
+        eprintln!("Invalid --size-colors: {} (the first size has to be smaller than the second)", pair);
+        std::process::exit(1);
+    }
+    icons.set_big_file(sizes.big_file);
+
+    if args.test_colors {
+        test_colors(&args.color, use_color);
+        print_scan_errors(&[]);
+        return;
+    }
+
+    if args.from_json.is_some() && args.git_rev.is_some() {
+        eprintln!("Error: --from-json and --git-rev both say where the tree comes from, pick one");
+        std::process::exit(1);
+    }
+    let snapshot = args.from_json.as_deref().map(|file| {
+        if args.path.is_some() {
+            eprintln!("Error: --from-json reads the tree from {}, leave out the path", file);
+            std::process::exit(1);
+        }
+        match load_json_tree(file) {
+            Ok(tree) => prune_tree(tree, args.depth, args.hidden, args.filter.as_deref()),
+            Err(e) => {
+                eprintln!("Error reading JSON tree {}: {}", file, e);
+                std::process::exit(1);
+            }
+        }
+    });
+    // a revision's tree is a snapshot too, just one git keeps for us. the path picks the repository and the folder in it
+    let snapshot = snapshot.or_else(|| {
+        let rev = args.git_rev.as_deref()?;
+        match git::rev_tree(Path::new(args.path.as_deref().unwrap_or(".")), rev) {
+            Ok(tree) => Some(prune_tree(tree, args.depth, args.hidden, args.filter.as_deref())),
+            Err(e) => {
+                eprintln!("Error reading git revision {}: {}", rev, e);
+                std::process::exit(1);
+            }
+        }
+    });
+    // so is an archive given as the path, its entry listing is the tree
+    let snapshot = snapshot.or_else(|| {
+        let file = Path::new(args.path.as_deref()?);
+        let kind = archive::archive_kind(&file.file_name()?.to_string_lossy()).filter(|_| file.is_file())?;
+        match archive::read_archive(file, kind) {
+            Ok(tree) => Some(prune_tree(tree, args.depth, args.hidden, args.filter.as_deref())),
+            Err(e) => {
+                eprintln!("Error reading archive {}: {}", file.display(), e);
+                std::process::exit(1);
+            }
+        }
+    });
+
+    match &args.command {
+        Some(Command::Diff(d)) => {
+            run_diff(d, &sizes);
+            return;
+        }
+        Some(Command::Dupes(d)) => {
+            run_dupes(d, &sizes, &icons);
+            return;
+        }
+        Some(Command::Top(t)) => {
+            run_top(t, &sizes);
+            return;
+        }
+        Some(Command::Manifest { action }) => {
+            run_manifest(action);
+            return;
+        }
+        Some(Command::Scaffold(sc)) => {
+            run_scaffold(sc);
+            print_scan_errors(&[]);
+            return;
+        }
+        Some(Command::Check(c)) => {
+            run_check(c);
+            return;
+        }
+        None => {}
+    }
+
+    let hashing = args.hash.as_deref().map(|algo| {
+        if snapshot.is_some() {
+            eprintln!("Error: --hash needs the files themselves, it can't be used with --from-json, --git-rev or an archive");
+            std::process::exit(1);
+        }
+        (parse_hash_algo(algo), args.hash_max_size.as_deref().map(|s| parse_size_flag(s, "--hash-max-size")))
+    });
+    if args.detect && snapshot.is_some() {
+        eprintln!("Error: --detect needs the files themselves, it can't be used with --from-json, --git-rev or an archive");
+        std::process::exit(1);
+    }
+    if args.git && snapshot.is_some() {
+        eprintln!("Error: --git needs the repository itself, it can't be used with --from-json, --git-rev or an archive");
+        std::process::exit(1);
+    }
+    let file_types: Option<Vec<String>> = args.file_type.as_deref().map(|list| {
+        let types: Vec<String> = list.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect();
+        if let Some(bad) = types.iter().find(|t| !icons::is_known_type(t)) {
+            eprintln!("Invalid type: {} (expected a kind like image, code or archive, a language, or a --detect label like elf)", bad);
+            std::process::exit(1);
+        }
+        types
+    });
+    // a snapshot has its --detect labels already, it can be filtered right away (the tui browses it too)
+    let snapshot = snapshot.map(|mut tree| {
+        if let Some(types) = &file_types {
+            filter_types(&mut tree, types);
+        }
+        tree
+    });
+
+    let mut interactive = args.interactive;
+    let path_str = if let Some(p) = args.path {
+        p
+    } else if let Some(tree) = &snapshot {
+        // the snapshot's root stands in for the path in titles and exported paths
+        if tree.name.is_empty() { ".".to_string() } else { tree.name.clone() }
+    } else {
+        interactive = true;
+        "C:\\".to_string()
+    };
+    let path = Path::new(&path_str);
+
+    if snapshot.is_none() && !path.exists() {
+        eprintln!("Error: Path does not exist: {}", path_str);
+        std::process::exit(1);
+    }
+
+    // outside a repository there's nothing to mark, the tree still prints
+    let git_status = match args.git {
+        true => match git::load(path) {
+            Ok(status) => Some(status),
+            Err(e) => {
+                record_error(path, ScanError { kind: ScanErrorKind::Io, message: format!("no git status: {}", e) });
+                None
+            }
+        },
+        false => None,
+    };
+
+    if interactive {
+        let source = match &snapshot {
+            Some(tree) => TuiSource::Snapshot(tree),
+            None => TuiSource::Disk {
+                show_hidden: args.hidden,
+                filter: args.filter.as_deref(),
+                loc: args.loc,
+                loc_cache: RefCell::new(HashMap::new()),
+                detect: args.detect,
+                types: file_types.as_deref(),
+                git: git_status.as_ref(),
+            },
+        };
+        if let Err(e) = run_tui(path, source, &icons) {
+            eprintln!("TUI error: {}", e);
+            std::process::exit(1);
+        }
+        print_scan_errors(&[]);
+    } else {
+        // --columns means csv columns when exporting csv/tsv, long listing columns otherwise
+        let is_table = matches!(export_format, Some("csv" | "tsv"));
+        let columns = match args.columns.as_deref() {
+            Some(list) if !is_table => {
+                let mut cols = Vec::new();
+                for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                    match Column::parse(name) {
+                        Some(c) => cols.push(c),
+                        None => {
+                            eprintln!("Invalid column: {} (expected perms, owner, group, size, mtime, loc or git)", name);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                cols
+            }
+            _ => {
+                let mut cols = long::DEFAULT_COLUMNS.to_vec();
+                if args.loc {
+                    cols.push(Column::Loc);
+                }
+                if args.git {
+                    cols.push(Column::Git);
+                }
+                cols
+            }
+        };
+        let csv_columns = match args.columns.as_deref() {
+            Some(list) if is_table => {
+                let mut cols = Vec::new();
+                for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                    match CsvColumn::parse(name) {
+                        Some(c) => cols.push(c),
+                        None => {
+                            eprintln!(
+                                "Invalid column: {} (expected name, path, abspath, parent, depth, ext, type, size, mtime, mode, owner, group, hash, lines, code, comments, blank, detected or git)",
+                                name
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                cols
+            }
+            _ => {
+                let mut cols = table::DEFAULT_CSV_COLUMNS.to_vec();
+                if args.loc {
+                    cols.extend(table::LOC_CSV_COLUMNS);
+                }
+                if args.detect {
+                    cols.push(CsvColumn::Detected);
+                }
+                if args.git {
+                    cols.push(CsvColumn::Git);
+                }
+                cols
+            }
+        };
+        let delimiter = match (args.delimiter.as_deref(), export_format) {
+            (Some(d), _) => match table::parse_delimiter(d) {
+                Some(b) => b,
+                None => {
+                    eprintln!("Invalid delimiter: {:?} (expected a single character or tab)", d);
+                    std::process::exit(1);
+                }
+            },
+            (None, Some("tsv")) => b'\t',
+            (None, _) => b',',
+        };
+        let csv_opts = || CsvOptions {
+            columns: csv_columns.clone(),
+            delimiter,
+            header: !args.no_header,
+            root: std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        };
+        let time_style = match args.time_style.as_str() {
+            "iso" => TimeStyle::Iso,
+            "relative" => TimeStyle::Relative,
+            other => {
+                eprintln!("Invalid time style: {} (expected iso or relative)", other);
+                std::process::exit(1);
+            }
+        };
+
+        // the html page has sortable metadata columns, so it always wants the metadata
+        let with_meta = args.long
+            || export_format == Some("html")
+            || (is_table && csv_columns.iter().any(|c| c.needs_meta()));
+
+        // the flat-ish exports don't need the whole tree, write them while walking.
+        // --collapse and --annotations look at the full tree so those still build it, --from-json has one already
+        let stream_format = match export_format {
+            Some(f @ ("json" | "csv" | "tsv" | "ndjson" | "jsonl")) => Some(f),
+            _ => None,
+        };
+        // hashing, line counts and sniffing go wide over a finished tree, so those skip streaming too
+        let needs_tree = args.collapse
+            || args.annotations.is_some()
+            || snapshot.is_some()
+            || hashing.is_some()
+            || args.loc
+            || args.detect
+            || args.git
+            || args.archives
+            || file_types.is_some();
+        if let (Some(format), false) = (stream_format, needs_tree) {
+            let opts = stream::WalkOptions {
+                max_depth: args.depth,
+                show_hidden: args.hidden,
+                filter: args.filter.as_deref(),
+                with_meta,
+            };
+            let stdout = io::stdout();
+            let out = io::BufWriter::new(stdout.lock());
+            let res = match format {
+                "json" => {
+                    let mut sink = stream::JsonSink::new(out);
+                    stream::walk(path, "", &opts, &mut sink).and_then(|errors| sink.finish().map(|_| errors))
+                }
+                "csv" | "tsv" => table::CsvSink::new(out, csv_opts())
+                    .and_then(|mut sink| stream::walk(path, "", &opts, &mut sink).and_then(|errors| sink.finish().map(|_| errors))),
+                _ => {
+                    let mut sink = stream::NdjsonSink { out };
+                    stream::walk(path, &path_str, &opts, &mut sink).and_then(|errors| sink.out.flush().map(|_| errors))
+                }
+            };
+            match res {
+                Ok(errors) => finish_scan(&errors),
+                Err(e) => {
+                    eprintln!("Error exporting to {}: {}", format, e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+
+        // a snapshot's errors are from whenever it was taken, only a scan happening now reports its own
+        let live = snapshot.is_none();
+        let tree = match snapshot {
+            Some(tree) => tree,
+            None => match build_tree(path, args.depth, args.hidden, args.filter.as_deref(), with_meta) {
+                Ok(mut t) => {
+                    if let Some((algo, max_size)) = hashing {
+                        let too_big = hash::fill_hashes(&mut t, path, algo, max_size);
+                        if too_big > 0 {
+                            eprintln!("Warning: left {} files over --hash-max-size without a hash", too_big);
+                        }
+                    }
+                    if args.loc {
+                        loc::fill_loc(&mut t, path);
+                    }
+                    if args.detect {
+                        detect::fill_detected(&mut t, path);
+                    }
+                    if let Some(status) = &git_status {
+                        status.fill(&mut t);
+                    }
+                    // after the fills, those only make sense for files on disk
+                    if args.archives {
+                        archive::expand_archives(&mut t, path, args.depth, args.hidden, args.filter.as_deref());
+                    }
+                    if let Some(types) = &file_types {
+                        filter_types(&mut t, types);
+                    }
+                    t
+                }
+                Err(e) => {
+                    eprintln!("Error reading directory: {}", e);
+                    std::process::exit(1);
+                }
+            },
+        };
+
+        let mut tree = tree;
+        if let Some(file) = args.annotations.as_deref() {
+            match notes::load_annotations(Path::new(file)) {
+                Ok(notes) => notes::apply_annotations(&mut tree, &notes),
+                Err(e) => {
+                    eprintln!("Error reading annotations {}: {}", file, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        let tree = if args.collapse {
+            collapse_tree(tree)
+        } else {
+            tree
+        };
+        let scan_errors = if live { scan_errors(&tree, path) } else { Vec::new() };
+
+        let stats = args.stats.as_deref().map(|format| {
+            let mut stats = stats::Stats { max_depth: args.depth, ..Default::default() };
+            let _ = stream::replay(&tree, "", 0, &mut stats);
+            (format, stats)
+        });
+        // json and csv are the stats on their own, no tree around them
+        if let Some((format @ ("json" | "csv"), stats)) = &stats {
+            let res = if *format == "json" { stats.export_json() } else { stats.export_csv() };
+            if let Err(e) = res {
+                eprintln!("Error exporting stats to {}: {}", format, e);
+                std::process::exit(1);
+            }
+            finish_scan(&scan_errors);
+            return;
+        }
+
+        if let Some(format) = export_format {
+            match format {
+                "json" => {
+                    if let Err(e) = export_json(&tree) {
+                        eprintln!("Error exporting to JSON: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                "csv" | "tsv" => {
+                    let out = io::BufWriter::new(io::stdout().lock());
+                    let res = table::CsvSink::new(out, csv_opts())
+                        .and_then(|mut sink| stream::replay(&tree, "", 0, &mut sink).and_then(|_| sink.finish()));
+                    if let Err(e) = res {
+                        eprintln!("Error exporting to CSV: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                "dot" | "mermaid" => {
+                    let opts = graph::GraphOptions {
+                        max_depth: args.graph_depth,
+                        sizes: if args.graph_sizes { Some(&sizes) } else { None },
+                        cluster: args.graph_cluster,
+                    };
+                    let name = root_name(&tree, path);
+                    let res = if format == "dot" {
+                        graph::export_dot(&tree, &name, &opts)
+                    } else {
+                        graph::export_mermaid(&tree, &name, &opts)
+                    };
+                    if let Err(e) = res {
+                        eprintln!("Error exporting to {}: {}", format, e);
+                        std::process::exit(1);
+                    }
+                }
+                "svg-treemap" | "svg-sunburst" => {
+                    let color = match args.svg_color.as_str() {
+                        "ext" | "extension" => svg::SvgColor::Extension,
+                        "depth" => svg::SvgColor::Depth,
+                        other => {
+                            eprintln!("Invalid svg color mode: {} (expected ext or depth)", other);
+                            std::process::exit(1);
+                        }
+                    };
+                    let opts = svg::SvgOptions {
+                        color,
+                        min_area: args.svg_min_area.max(0.0),
+                        sizes: &sizes,
+                    };
+                    let name = root_name(&tree, path);
+                    let res = if format == "svg-treemap" {
+                        svg::export_treemap(&tree, &name, &opts)
+                    } else {
+                        svg::export_sunburst(&tree, &name, &opts)
+                    };
+                    if let Err(e) = res {
+                        eprintln!("Error exporting to {}: {}", format, e);
+                        std::process::exit(1);
+                    }
+                }
+                "yaml" | "yml" | "toml" | "xml" | "ndjson" | "jsonl" => {
+                    let res = match format {
+                        "yaml" | "yml" => formats::export_yaml(&tree),
+                        "toml" => formats::export_toml(&tree),
+                        "xml" => formats::export_xml(&tree, &path_str),
+                        _ => formats::export_ndjson(&tree, &path_str),
+                    };
+                    if let Err(e) = res {
+                        eprintln!("Error exporting to {}: {}", format, e);
+                        std::process::exit(1);
+                    }
+                }
+                "html" => {
+                    if let Err(e) = html::export_html(&tree, &root_name(&tree, path), sizes.si) {
+                        eprintln!("Error exporting to HTML: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                _ => {
+                    eprintln!("Invalid export format: {}", format);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            if args.relative_sizes || config.sizes.relative {
+                sizes.scale_to(largest_file(&tree));
+                icons.set_big_file(sizes.big_file);
+            }
+            let layout = if args.long {
+                Some(LongLayout::new(columns, time_style, &sizes, &tree))
+            } else {
+                None
+            };
+            let opts = PrintOptions {
+                icons: &icons,
+                long: layout.as_ref(),
+                sizes: &sizes,
+                show_sizes: !doc_format,
+                dir_slash: doc_format,
+            };
+            if doc_format {
+                // docs want the classic `tree` look: root on top, children right under it
+                let root_label = root_name(&tree, path);
+                if args.format == "markdown" && args.md_style == "list" {
+                    markdown::print_list(&tree, &root_label, args.md_links.as_deref());
+                } else {
+                    if args.format == "markdown" {
+                        println!("```text");
+                    }
+                    let root_line = format!("{}/", root_label.trim_end_matches(['/', '\\']));
+                    match &tree.note {
+                        Some(note) => println!("{}  # {}", root_line, note),
+                        None => println!("{}", root_line),
+                    }
+                    for (i, child) in tree.children.iter().enumerate() {
+                        print_tree(child, "", i == tree.children.len() - 1, &opts);
+                    }
+                    if args.format == "markdown" {
+                        println!("```");
+                    }
+                }
+            } else {
+                match opts.long {
+                    Some(layout) => println!("{}{}", layout.blank(), path.display()),
+                    None => println!("{}", path.display()),
+                }
+                print_tree(&tree, "", true, &opts);
+            }
+            if let Some((_, stats)) = &stats {
+                stats.print(&sizes);
+            }
+        }
+        finish_scan(&scan_errors);
+    }
+}
+
+/// everything in the tree the scan couldn't read, by its path on disk
+fn scan_errors(tree: &TreeNode, root: &Path) -> Vec<(PathBuf, ScanError)> {
+    let mut out = Vec::new();
+    collect_scan_errors(tree, root, &mut out);
+    out
+}
+
+fn collect_scan_errors(node: &TreeNode, path: &Path, out: &mut Vec<(PathBuf, ScanError)>) {
+    if let Some(error) = &node.error {
+        out.push((path.to_path_buf(), error.clone()));
+    }
+    for child in &node.children {
+        collect_scan_errors(child, &path.join(&child.name), out);
+    }
+}
+
+// what went wrong next to the tree rather than in it, a broken config or --git outside a repository.
+// they're loaded long before any scan starts, so they wait here for its summary
+static SIDE_ERRORS: Mutex<Vec<(PathBuf, ScanError)>> = Mutex::new(Vec::new());
+
+fn record_error(path: &Path, error: ScanError) {
+    if let Ok(mut side) = SIDE_ERRORS.lock() {
+        side.push((path.to_path_buf(), error));
+    }
+}
+
+fn with_side_errors(errors: &[(PathBuf, ScanError)]) -> Vec<(PathBuf, ScanError)> {
+    let mut all = SIDE_ERRORS.lock().map(|side| side.clone()).unwrap_or_default();
+    all.extend_from_slice(errors);
+    all
+}
+
+/// the summary goes to stderr so it never ends up in an export
+fn print_scan_errors(errors: &[(PathBuf, ScanError)]) {
+    let errors = with_side_errors(errors);
+    if errors.is_empty() {
+        return;
+    }
+    eprintln!();
+    eprintln!("{} {} couldn't be read:", errors.len(), if errors.len() == 1 { "entry" } else { "entries" });
+    for (path, error) in &errors {
+        eprintln!("  {} ({}): {}", path.display(), error.label(), error.message);
+    }
+}
+
+/// exit 0 for a complete scan, 2 when parts of the tree are missing. 1 stays for scans that failed outright
+fn finish_scan(errors: &[(PathBuf, ScanError)]) {
+    print_scan_errors(errors);
+    if !with_side_errors(errors).is_empty() {
+        std::process::exit(2);
+    }
+}
+
+/// name to show for the scanned folder, `.` has no file name so use the real one
+fn root_name(tree: &TreeNode, path: &Path) -> String {
+    if !tree.name.is_empty() {
+        return tree.name.clone();
+    }
+    std::fs::canonicalize(path)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn build_tree(path: &Path, max_depth: Option<usize>, show_hidden: bool, filter: Option<&str>, with_meta: bool) -> std::io::Result<TreeNode> {
+    let entries = get_entries(path, show_hidden, filter)?;
+
+    let mut children = Vec::new();
+    for entry in entries {
+        let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
+        let child = if is_dir && max_depth.is_none_or(|d| d > 0) {
+            let new_depth = max_depth.map(|d| d - 1);
+            // one folder we can't read shouldn't cost the whole tree, it's kept with the reason instead
+            build_tree(&entry.path(), new_depth, show_hidden, filter, with_meta).unwrap_or_else(|e| TreeNode {
+                error: Some(ScanError::from_io(&e)),
+                ..leaf_node(&entry, with_meta)
+            })
+        } else {
+            leaf_node(&entry, with_meta)
+        };
+
+        children.push(child);
+    }
+
+    let mut node = dir_node(path, with_meta);
+    node.children = children;
+    Ok(node)
+}
+
+/// node for an entry we won't descend into (files, and folders past --depth)
+fn leaf_node(entry: &std::fs::DirEntry, with_meta: bool) -> TreeNode {
+    let file_type = entry.file_type().ok();
+    let is_dir = file_type.is_some_and(|ft| ft.is_dir());
+    let special = match file_type {
+        Some(ft) if ft.is_symlink() => Some(Special::Symlink),
+        Some(ft) if !ft.is_dir() && !ft.is_file() => Some(special_kind(ft)),
+        _ => None,
+    };
+    let md = entry.metadata();
+    let mut node = TreeNode {
+        name: entry.file_name().to_string_lossy().to_string(),
+        is_dir,
+        size: if is_dir { None } else { md.as_ref().ok().map(|m| m.len()) },
+        error: md.as_ref().err().map(ScanError::from_io),
+        special,
+        ..Default::default()
+    };
+    let md = md.ok();
+    if let (true, Some(md)) = (with_meta, md) {
+        meta::fill_metadata(&mut node, &md);
+    }
+    node
+}
+
+#[cfg(unix)]
+fn special_kind(ft: std::fs::FileType) -> Special {
+    use std::os::unix::fs::FileTypeExt;
+    if ft.is_fifo() {
+        Special::Fifo
+    } else if ft.is_socket() {
+        Special::Socket
+    } else if ft.is_block_device() || ft.is_char_device() {
+        Special::Device
+    } else {
+        Special::Other
+    }
+}
 
-    let export_format = if args.json { Some("json") } else { args.export.as_deref() };
+#[cfg(windows)]
+fn special_kind(_ft: std::fs::FileType) -> Special {
+    Special::Other
+}
 
-    if args.check_path {
-        let exe_path = std::env::current_exe().unwrap();
-        let exe_name = exe_path.file_name().unwrap().to_string_lossy();
-        let path_var = std::env::var_os("PATH").unwrap_or_default();
-        let paths: Vec<std::path::PathBuf> = std::env::split_paths(&path_var).collect();
-        let found_path = paths.iter().find(|p| p.join(exe_name.as_ref()).exists());
-        if let Some(p) = found_path {
-            println!("yes {}", p.join(exe_name.as_ref()).display());
-        } else {
-            println!("no");
+/// node for a folder itself, children get filled in by whoever walks it
+fn dir_node(path: &Path, with_meta: bool) -> TreeNode {
+    let mut node = TreeNode {
+        name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        is_dir: true,
+        size: None,
+        ..Default::default()
+    };
+    if with_meta {
+        if let Ok(md) = std::fs::symlink_metadata(path) {
+            meta::fill_metadata(&mut node, &md);
         }
-        return;
     }
+    node
+}
 
-    if args.test_colors {
-        test_colors();
-        return;
+fn run_diff(d: &DiffArgs, sizes: &SizeStyle) {
+    let scan_errs = RefCell::new(Vec::new());
+    let load = |arg: &str| -> diff::Side {
+        let path = Path::new(arg);
+        let res = if path.is_dir() {
+            build_tree(path, None, d.hidden, None, true).map(|tree| {
+                scan_errs.borrow_mut().extend(scan_errors(&tree, path));
+                diff::Side { label: arg.to_string(), tree, dir: Some(path.to_path_buf()) }
+            })
+        } else if path.exists() || arg == "-" {
+            load_json_tree(arg).map(|tree| diff::Side { label: arg.to_string(), tree, dir: None })
+        } else {
+            // not a file, maybe a branch or tag of the repository we're in
+            match git::rev_tree(Path::new("."), arg) {
+                Ok(tree) => Ok(diff::Side { label: arg.to_string(), tree: prune_tree(tree, None, d.hidden, None), dir: None }),
+                Err(_) => {
+                    eprintln!("Error: Path does not exist and isn't a git revision: {}", arg);
+                    std::process::exit(1);
+                }
+            }
+        };
+        res.unwrap_or_else(|e| {
+            eprintln!("Error reading {}: {}", arg, e);
+            std::process::exit(1);
+        })
+    };
+    let (a, b) = (load(&d.before), load(&d.after));
+    let opts = diff::DiffOptions { hash: d.hash, all: d.all, sizes };
+    let root = diff::diff_trees(&a, &b, &opts);
+    if d.json {
+        if let Err(e) = diff::export_diff_json(&root, &a, &b) {
+            eprintln!("Error exporting diff to JSON: {}", e);
+            std::process::exit(1);
+        }
+    } else {
+        diff::print_diff(&root, &opts);
     }
+    finish_scan(&scan_errs.into_inner());
+}
 
-    let mut interactive = args.interactive;
-    let path_str = if let Some(p) = args.path {
-        p
-    } else {
-        interactive = true;
-        "C:\\".to_string()
+fn run_dupes(d: &DupesArgs, sizes: &SizeStyle, icons: &IconSet) {
+    let opts = dupes::DupeOptions {
+        min_size: d.min_size.as_deref().map(|s| parse_size_flag(s, "--min-size")).unwrap_or(1),
+        merge_hardlinks: d.merge_hardlinks,
+        algo: parse_hash_algo(&d.algo),
     };
-    let path = Path::new(&path_str);
-
-    if !path.exists() {
-        eprintln!("Error: Path does not exist: {}", path_str);
+    let root = Path::new(&d.path);
+    let tree = build_tree(root, None, d.hidden, None, false).unwrap_or_else(|e| {
+        eprintln!("Error reading directory: {}", e);
         std::process::exit(1);
+    });
+    let errors = scan_errors(&tree, root);
+    let mut groups = dupes::find_dupes(root, &tree, &opts);
+    if d.json {
+        if let Err(e) = dupes::export_dupes_json(&groups, &d.path) {
+            eprintln!("Error exporting to JSON: {}", e);
+            std::process::exit(1);
+        }
+    } else if d.interactive {
+        if let Err(e) = dupes::run_dupes_tui(root, &mut groups, sizes) {
+            eprintln!("TUI error: {}", e);
+            std::process::exit(1);
+        }
+    } else if d.tree {
+        let dupes_tree = dupes::dupes_tree(&tree, &groups);
+        let opts = PrintOptions { icons, long: None, sizes, show_sizes: true, dir_slash: false };
+        println!("{}", root_name(&tree, root).blue().bold());
+        let count = dupes_tree.children.len();
+        for (i, child) in dupes_tree.children.iter().enumerate() {
+            print_tree(child, "", i == count - 1, &opts);
+        }
+        let wasted: u64 = groups.iter().map(|g| g.wasted).sum();
+        println!();
+        println!("{} groups, {} wasted", groups.len(), sizes.format(wasted).bold());
+    } else {
+        dupes::print_groups(&groups, sizes);
     }
+    finish_scan(&errors);
+}
 
-    if interactive {
-        if let Err(e) = run_tui(path, args.hidden, args.filter.as_deref()) {
-            eprintln!("TUI error: {}", e);
+fn run_top(t: &TopArgs, sizes: &SizeStyle) {
+    let by = top::TopBy::parse(&t.by).unwrap_or_else(|| {
+        eprintln!("Invalid --by: {} (expected size, count or mtime)", t.by);
+        std::process::exit(1);
+    });
+    if t.n == 0 {
+        eprintln!("Invalid -n: 0 (expected at least 1)");
+        std::process::exit(1);
+    }
+    let root = Path::new(&t.path);
+    if !root.is_dir() {
+        eprintln!("Error: Path does not exist: {}", t.path);
+        std::process::exit(1);
+    }
+    let opts = stream::WalkOptions { max_depth: None, show_hidden: t.hidden, filter: None, with_meta: by == top::TopBy::Mtime };
+    let mut sink = top::TopSink::new(by, t.n);
+    let errors = stream::walk(root, "", &opts, &mut sink).unwrap_or_else(|e| {
+        eprintln!("Error reading directory: {}", e);
+        std::process::exit(1);
+    });
+    if t.json {
+        if let Err(e) = sink.export_json() {
+            eprintln!("Error exporting to JSON: {}", e);
             std::process::exit(1);
         }
     } else {
-        let tree = match build_tree(path, args.depth, args.hidden, args.filter.as_deref()) {
-            Ok(t) => t,
-            Err(e) => {
+        sink.print_table(sizes);
+    }
+    finish_scan(&errors);
+}
+
+fn parse_hash_algo(name: &str) -> hash::HashAlgo {
+    hash::HashAlgo::parse(name).unwrap_or_else(|| {
+        eprintln!("Invalid hash algorithm: {} (expected sha256, blake3 or xxh3)", name);
+        std::process::exit(1);
+    })
+}
+
+fn parse_size_flag(text: &str, what: &str) -> u64 {
+    parse_size(text).unwrap_or_else(|| {
+        eprintln!("Invalid size for {}: {}", what, text);
+        std::process::exit(1);
+    })
+}
+
+fn run_manifest(action: &ManifestAction) {
+    match action {
+        ManifestAction::Create(c) => {
+            let algo = parse_hash_algo(&c.algo);
+            let max_size = c.hash_max_size.as_deref().map(|s| parse_size_flag(s, "--hash-max-size"));
+            if !matches!(c.format.as_str(), "sums" | "json") {
+                eprintln!("Invalid manifest format: {} (expected sums or json)", c.format);
+                std::process::exit(1);
+            }
+            let root = Path::new(&c.path);
+            let mut tree = build_tree(root, None, c.hidden, None, false).unwrap_or_else(|e| {
                 eprintln!("Error reading directory: {}", e);
                 std::process::exit(1);
+            });
+            let too_big = hash::fill_hashes(&mut tree, root, algo, max_size);
+            if too_big > 0 {
+                eprintln!("Warning: left {} files over --hash-max-size out of the manifest", too_big);
             }
-        };
-
-        let tree = if args.collapse {
-            collapse_tree(tree)
-        } else {
-            tree
-        };
-
-        if let Some(format) = export_format {
-            match format {
-                "json" => {
-                    if let Err(e) = export_json(&tree) {
-                        eprintln!("Error exporting to JSON: {}", e);
-                        std::process::exit(1);
+            // files that couldn't be hashed are missing from the manifest too
+            let errors = scan_errors(&tree, root);
+            let mut files = std::collections::BTreeMap::new();
+            manifest::hashed_files(&tree, "", &mut files);
+            // don't list the manifest in itself when it's written inside the folder
+            if let Some(out) = c.output.as_deref() {
+                let out = std::fs::canonicalize(Path::new(out).parent().unwrap_or(Path::new("."))).ok().map(|d| d.join(Path::new(out).file_name().unwrap_or_default()));
+                let root = std::fs::canonicalize(root).ok();
+                if let (Some(out), Some(root)) = (out, root) {
+                    if let Ok(rel) = out.strip_prefix(&root) {
+                        files.remove(&rel.to_string_lossy().replace('\\', "/"));
                     }
                 }
-                "csv" => {
-                    if let Err(e) = export_csv(&tree) {
-                        eprintln!("Error exporting to CSV: {}", e);
-                        std::process::exit(1);
-                    }
-                }
-                _ => {
-                    eprintln!("Invalid export format: {}", format);
-                    std::process::exit(1);
-                }
             }
-        } else {
-            println!("{}", path.display());
-            print_tree(&tree, "", true);
+            let res = match c.output.as_deref() {
+                Some(out) => std::fs::File::create(out)
+                    .and_then(|f| manifest::write_manifest(&mut io::BufWriter::new(f), &files, algo, c.format == "json")),
+                None => manifest::write_manifest(&mut io::BufWriter::new(io::stdout().lock()), &files, algo, c.format == "json"),
+            };
+            if let Err(e) = res {
+                eprintln!("Error writing manifest: {}", e);
+                std::process::exit(1);
+            }
+            // a manifest of part of the folder would fail every verify after it
+            finish_scan(&errors);
+        }
+        ManifestAction::Verify(v) => {
+            let given = v.algo.as_deref().map(parse_hash_algo);
+            let max_size = v.hash_max_size.as_deref().map(|s| parse_size_flag(s, "--hash-max-size"));
+            let text = std::fs::read_to_string(&v.manifest).unwrap_or_else(|e| {
+                eprintln!("Error reading manifest {}: {}", v.manifest, e);
+                std::process::exit(1);
+            });
+            let (algo, expected) = manifest::read_manifest(&text, given).unwrap_or_else(|e| {
+                eprintln!("Error reading manifest {}: {}", v.manifest, e);
+                std::process::exit(1);
+            });
+            let manifest_path = Path::new(&v.manifest);
+            let root = match v.root.as_deref() {
+                Some(r) => Path::new(r).to_path_buf(),
+                None => manifest_path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf(),
+            };
+            let mut tree = build_tree(&root, None, v.hidden, None, false).unwrap_or_else(|e| {
+                eprintln!("Error reading directory: {}", e);
+                std::process::exit(1);
+            });
+            let errors = scan_errors(&tree, &root);
+            // the manifest sitting in the folder it describes isn't an extra file
+            if v.root.is_none() {
+                let name = manifest_path.file_name().unwrap_or_default().to_string_lossy();
+                tree.children.retain(|c| c.is_dir || c.name != name);
+            }
+            let report = manifest::verify(&root, &tree, &expected, algo, max_size);
+            manifest::print_report(&report, v.ignore_extra);
+            let failed = !report.missing.is_empty() || !report.corrupted.is_empty() || (!v.ignore_extra && !report.extra.is_empty());
+            if failed {
+                print_scan_errors(&errors);
+                std::process::exit(1);
+            }
+            // an unreadable folder could be hiding extra files
+            finish_scan(&errors);
         }
     }
 }
 
-fn build_tree(path: &Path, max_depth: Option<usize>, show_hidden: bool, filter: Option<&str>) -> std::io::Result<TreeNode> {
-    let entries = match std::fs::read_dir(path) {
-        Ok(dir) => dir.filter_map(|e| e.ok()).collect::<Vec<_>>(),
+fn run_scaffold(sc: &ScaffoldArgs) {
+    let text = if sc.spec == "-" {
+        io::read_to_string(io::stdin().lock())
+    } else {
+        std::fs::read_to_string(&sc.spec)
+    };
+    let tree = text.map_err(|e| e.to_string()).and_then(|text| scaffold::parse_spec(&text)).unwrap_or_else(|e| {
+        eprintln!("Error reading spec {}: {}", sc.spec, e);
+        std::process::exit(1);
+    });
+    let templates = sc.templates.as_deref().map(|dir| {
+        if !Path::new(dir).is_dir() {
+            eprintln!("Error: Path does not exist: {}", dir);
+            std::process::exit(1);
+        }
+        let vars = sc
+            .vars
+            .iter()
+            .map(|v| match v.split_once('=') {
+                Some((key, value)) => (key.trim().to_string(), value.to_string()),
+                None => {
+                    eprintln!("Invalid --var: {} (expected KEY=VALUE)", v);
+                    std::process::exit(1);
+                }
+            })
+            .collect();
+        scaffold::Templates { dir: Path::new(dir).to_path_buf(), vars }
+    });
+    let into = Path::new(&sc.into);
+    let steps = scaffold::plan(&tree, into).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    scaffold::print_plan(&steps, sc.force, templates.as_ref());
+    // nothing gets written unless all of it can be
+    let clashes = steps.iter().filter(|s| s.existing == scaffold::Existing::Clash).count();
+    let existing = steps.iter().filter(|s| s.existing == scaffold::Existing::File).count();
+    if clashes > 0 {
+        eprintln!("Error: {} entries clash with a file or folder of the other kind already there", clashes);
+        std::process::exit(1);
+    }
+    if existing > 0 && !sc.force {
+        eprintln!("Error: {} files already exist in {}, use --force to overwrite them", existing, sc.into);
+        std::process::exit(1);
+    }
+    if sc.dry_run {
+        return;
+    }
+    match scaffold::apply(&steps, into, templates.as_ref()) {
+        Ok((dirs, files)) => {
+            println!();
+            println!("created {} folders and {} files in {}", dirs, files, sc.into);
+        }
         Err(e) => {
-            eprintln!("Warning: access denied for {}: {}", path.display(), e);
-            Vec::new()
+            eprintln!("Error creating files in {}: {}", sc.into, e);
+            std::process::exit(1);
         }
-    };
-    let mut entries = entries;
-    // skip hidden files if not showing them, dotfiles smh
-    if !show_hidden {
-        entries.retain(|e| !e.file_name().to_string_lossy().starts_with('.'));
     }
-    entries.sort_by_key(|e| e.path());
+}
 
-    if let Some(pattern) = filter {
-        let pat = glob::Pattern::new(pattern).unwrap_or(glob::Pattern::new("*").unwrap());
-        entries.retain(|e| {
-            let is_dir = e.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-            is_dir || pat.matches(&e.file_name().to_string_lossy())
-        });
+fn run_check(c: &CheckArgs) {
+    if !matches!(c.format.as_str(), "text" | "json" | "sarif") {
+        eprintln!("Invalid check format: {} (expected text, json or sarif)", c.format);
+        std::process::exit(1);
     }
+    let rules = std::fs::read_to_string(&c.rules).map_err(|e| e.to_string()).and_then(|text| rules::load_rules(&text)).unwrap_or_else(|e| {
+        eprintln!("Error reading rules {}: {}", c.rules, e);
+        std::process::exit(1);
+    });
+    let root = Path::new(&c.path);
+    if !root.is_dir() {
+        eprintln!("Error: Path does not exist: {}", c.path);
+        std::process::exit(1);
+    }
+    let tree = build_tree(root, None, c.hidden, None, false).unwrap_or_else(|e| {
+        eprintln!("Error reading directory: {}", e);
+        std::process::exit(1);
+    });
+    let errors = scan_errors(&tree, root);
+    let report = rules::check(&tree, &rules);
+    let res = match c.format.as_str() {
+        "json" => rules::export_json(&report),
+        "sarif" => rules::export_sarif(&report, &rules),
+        _ => {
+            rules::print_report(&report);
+            Ok(())
+        }
+    };
+    if let Err(e) = res {
+        eprintln!("Error exporting to {}: {}", c.format, e);
+        std::process::exit(1);
+    }
+    // warnings are reported but don't fail the build
+    if report.count(rules::Level::Error) > 0 {
+        print_scan_errors(&errors);
+        std::process::exit(1);
+    }
+    // no violations in what could be read isn't a pass, a required file may be in the folder we couldn't open
+    finish_scan(&errors);
+}
 
-    let mut children = Vec::new();
-    for entry in entries {
-        let name = entry.file_name().to_string_lossy().to_string();
-        let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-        let size = if is_dir {
-            None
-        } else {
-            entry.metadata().ok().map(|m| m.len())
-        };
-
-        let child = if is_dir && max_depth.map_or(true, |d| d > 0) {
-            let new_depth = max_depth.map(|d| d - 1);
-            build_tree(&entry.path(), new_depth, show_hidden, filter)?
-        } else {
-            TreeNode {
-                name: name.clone(),
-                is_dir,
-                size,
-                children: Vec::new(),
-            }
-        };
+/// a tree saved with --json, from a file or stdin
+fn load_json_tree(file: &str) -> io::Result<TreeNode> {
+    let tree = if file == "-" {
+        serde_json::from_reader(io::BufReader::new(io::stdin().lock()))?
+    } else {
+        serde_json::from_reader(io::BufReader::new(std::fs::File::open(file)?))?
+    };
+    Ok(tree)
+}
 
-        children.push(child);
+// same order a walk of the disk gives
+fn sort_tree(node: &mut TreeNode) {
+    node.children.sort_by(|a, b| a.name.cmp(&b.name));
+    for child in node.children.iter_mut() {
+        sort_tree(child);
     }
+}
 
-    Ok(TreeNode {
-        name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
-        is_dir: true,
-        size: None,
-        children,
-    })
+/// --depth, --hidden and --filter for a tree that didn't come from walking the disk
+fn prune_tree(node: TreeNode, max_depth: Option<usize>, show_hidden: bool, filter: Option<&str>) -> TreeNode {
+    let pat = filter.map(|p| glob::Pattern::new(p).unwrap_or(glob::Pattern::new("*").unwrap()));
+    let mut node = node;
+    let children = std::mem::take(&mut node.children);
+    node.children = children
+        .into_iter()
+        .filter(|c| show_hidden || !c.name.starts_with('.'))
+        .filter(|c| c.is_dir || pat.as_ref().is_none_or(|p| p.matches(&c.name)))
+        .map(|c| match max_depth {
+            // same as build_tree: folders at the depth limit are listed but not opened
+            Some(0) => TreeNode { children: Vec::new(), ..c },
+            _ => prune_tree(c, max_depth.map(|d| d - 1), show_hidden, filter),
+        })
+        .collect();
+    node
+}
+
+/// --type: drop the files that aren't one of `types`. folders stay, same as with --filter
+fn filter_types(node: &mut TreeNode, types: &[String]) {
+    for child in node.children.iter_mut() {
+        filter_types(child, types);
+    }
+    // an --archives archive stays while something inside it matches
+    node.children.retain(|c| c.is_dir || !c.children.is_empty() || types.iter().any(|t| icons::type_matches(&c.name, c.detected.as_deref(), t)));
 }
 
 fn collapse_tree(node: TreeNode) -> TreeNode {
-    let name = node.name;
-    let is_dir = node.is_dir;
-    let size = node.size;
-    let children = node.children;
+    let mut node = node;
+    let children = std::mem::take(&mut node.children);
     let mut new_children = Vec::new();
     for child in children {
         let collapsed = collapse_tree(child);
@@ -320,60 +1989,81 @@ fn collapse_tree(node: TreeNode) -> TreeNode {
     }
     if new_children.len() == 1 && new_children[0].is_dir {
         let child = new_children.into_iter().next().unwrap();
+        // keep the deepest folder's metadata, that's the one the name ends with
         TreeNode {
-            name: format!("{}/{}", name, child.name),
+            name: format!("{}/{}", node.name, child.name),
             is_dir: true,
             size: None,
-            children: child.children,
+            ..child
         }
     } else {
         TreeNode {
-            name,
-            is_dir,
-            size,
             children: new_children,
+            ..node
         }
     }
 }
 
-fn print_tree(node: &TreeNode, prefix: &str, is_last: bool) {
+struct PrintOptions<'a> {
+    icons: &'a IconSet,
+    long: Option<&'a LongLayout<'a>>,
+    sizes: &'a SizeStyle,
+    show_sizes: bool,
+    dir_slash: bool,
+}
+
+fn print_tree(node: &TreeNode, prefix: &str, is_last: bool, opts: &PrintOptions) {
     let connector = if is_last { "└── " } else { "├── " };
-    let color = if node.is_dir { Color::Blue } else { Color::White };
-    let icon = if node.is_dir { "[DIR] ".to_string() } else { get_icon_for_name(&node.name) };
+    let color = match node.is_dir {
+        _ if node.error.is_some() => Color::Red,
+        true => Color::Blue,
+        // only --detect knows an extensionless binary is a program, so only it turns them green
+        false if detected_program(node) => Color::Green,
+        false => Color::White,
+    };
+    let icon = opts.icons.icon(&node.name, node.is_dir, node.size, node.detected.as_deref());
+    let mut name = node.name.clone();
+    if opts.dir_slash && node.is_dir {
+        name.push('/');
+    }
+    if let Some(term_width) = opts.long.and_then(|l| l.term_width()) {
+        // long lines get cut instead of wrapping and wrecking the columns
+        let used = opts.long.map_or(0, |l| l.width()) + long::display_width(prefix) + 4 + long::display_width(&icon);
+        name = long::truncate_to_width(&name, term_width.saturating_sub(used));
+    }
     let icon_colored = icon.color(color);
-    let name_colored = node.name.color(color);
+    let name_colored = name.color(color);
     let mut display = format!("{}{}", icon_colored, name_colored);
-    if let Some(size) = node.size {
-        let size_str = format_size(size);
-        let size_color = get_size_color(size);
+    // the long listing has a git column, the plain tree gets the marker up front where it lines up
+    if let (Some(state), None) = (node.git, opts.long) {
+        let marker = if state == git::GitState::Clean { " " } else { state.marker() };
+        display = format!("{} {}", marker.color(state.color()), display);
+    }
+    if let (Some(size), None, true) = (node.size, opts.long, opts.show_sizes) {
+        let size_str = opts.sizes.format(size);
+        let size_color = opts.sizes.color(size);
         display.push_str(&format!(" ({})", size_str.color(size_color)));
     }
-    println!("{}{}{}", prefix, connector, display);
+    if let (Some(loc), None) = (&node.loc, opts.long) {
+        display.push_str(&format!(" {}", format!("[{} loc]", loc.code).dimmed()));
+    }
+    if let Some((_, hex)) = node.hash.as_deref().and_then(hash::split_tagged) {
+        // the first dozen hex digits are plenty to eyeball, the exports have the full thing
+        display.push_str(&format!(" {}", hex.get(..12).unwrap_or(hex).dimmed()));
+    }
+    if let Some(error) = &node.error {
+        display.push_str(&format!(" {}", format!("[{}]", error.label()).red()));
+    }
+    if let Some(note) = &node.note {
+        display.push_str(&format!("  {}", format!("# {}", note).dimmed()));
+    }
+    let columns = opts.long.map(|l| l.cells(node)).unwrap_or_default();
+    println!("{}{}{}{}", columns, prefix, connector, display);
 
     let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
     for (i, child) in node.children.iter().enumerate() {
         let child_is_last = i == node.children.len() - 1;
-        print_tree(child, &new_prefix, child_is_last);
-    }
-}
-
-fn get_icon_for_name(name: &str) -> String {
-    if let Some(ext) = Path::new(name).extension() {
-        let ext_str = ext.to_string_lossy().to_lowercase();
-        match ext_str.as_str() {
-            "py" => "[.py] ".to_string(),
-            "rs" => "[.rs] ".to_string(),
-            "js" => "[.js] ".to_string(),
-            "ts" => "[.ts] ".to_string(),
-            "html" => "[.html] ".to_string(),
-            "css" => "[.css] ".to_string(),
-            "md" => "[.md] ".to_string(),
-            "txt" => "[.txt] ".to_string(),
-            "exe" | "bat" | "cmd" => "[EXEC] ".to_string(),
-            _ => format!("[{}] ", ext_str),
-        }
-    } else {
-        "[FILE] ".to_string()
+        print_tree(child, &new_prefix, child_is_last, opts);
     }
 }
 
@@ -383,7 +2073,20 @@ fn export_json(tree: &TreeNode) -> std::io::Result<()> {
     Ok(())
 }
 
-fn test_colors() {
+fn test_colors(mode: &str, use_color: bool) {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    let env_or_unset = |name: &str| std::env::var(name).unwrap_or_else(|_| "(not set)".to_string());
+    let support = detect_color_support();
+    println!("{}", "Color capabilities:".bold());
+    println!("  stdout is a terminal: {}", yes_no(io::stdout().is_terminal()));
+    println!("  TERM:                 {}", env_or_unset("TERM"));
+    println!("  COLORTERM:            {}", env_or_unset("COLORTERM"));
+    println!("  NO_COLOR:             {}", env_or_unset("NO_COLOR"));
+    println!("  CLICOLOR_FORCE:       {}", env_or_unset("CLICOLOR_FORCE"));
+    println!("  detected support:     {}", support.describe());
+    println!("  colors enabled:       {} (--color={})", yes_no(use_color), mode);
+    println!();
+
     println!("{}", "Testing colors:".bold());
     println!("{}", "Red text".red());
     println!("{}", "Green text".green());
@@ -400,43 +2103,40 @@ fn test_colors() {
     println!("{}", "Bright cyan".bright_cyan());
     println!("{}", "Bright magenta".bright_magenta());
     println!("{}", "Bright white".bright_white());
-}
 
-fn export_csv(tree: &TreeNode) -> std::io::Result<()> {
-    let mut wtr = csv::Writer::from_writer(std::io::stdout());
-    wtr.write_record(&["path", "type", "size"])?;
-    collect_entries(tree, "", &mut |path, typ, size| {
-        let size_str = size.map(|s| s.to_string()).unwrap_or_default();
-        wtr.write_record(&[path, typ, &size_str]).unwrap();
-    });
-    wtr.flush()?;
-    Ok(())
-}
-
-fn collect_entries<F>(node: &TreeNode, current_path: &str, func: &mut F)
-where
-    F: FnMut(&str, &str, Option<u64>),
-{
-    let path = if current_path.is_empty() {
-        node.name.clone()
-    } else {
-        format!("{}/{}", current_path, node.name)
-    };
-    let typ = if node.is_dir { "directory" } else { "file" };
-    func(&path, typ, node.size);
-    for child in &node.children {
-        collect_entries(child, &path, func);
+    if !use_color {
+        return;
+    }
+    if support == ColorSupport::Ansi256 || support == ColorSupport::TrueColor {
+        println!();
+        println!("{}", "256-color palette:".bold());
+        for row in 0..16u16 {
+            let line: String = (0..16u16)
+                .map(|col| format!("\x1b[48;5;{}m  ", row * 16 + col))
+                .collect();
+            println!("{}\x1b[0m", line);
+        }
+    }
+    if support == ColorSupport::TrueColor {
+        println!();
+        println!("{}", "Truecolor gradient:".bold());
+        let gradient: String = (0..64u32)
+            .map(|i| {
+                let r = (255 - i * 4) as u8;
+                let g = (i * 4) as u8;
+                let b = 128u8;
+                " ".on_truecolor(r, g, b).to_string()
+            })
+            .collect();
+        println!("{}", gradient);
     }
 }
 
+/// a folder's entries, hidden and --filter ones left out. a folder that can't be read is an error,
+/// the tree walks turn that into `TreeNode::error` on the folder
 fn get_entries(path: &Path, show_hidden: bool, filter: Option<&str>) -> io::Result<Vec<std::fs::DirEntry>> {
-    let mut entries = match std::fs::read_dir(path) {
-        Ok(dir) => dir.filter_map(|e| e.ok()).collect::<Vec<_>>(),
-        Err(e) => {
-            eprintln!("Warning: access denied for {}: {}", path.display(), e);
-            Vec::new()
-        }
-    };
+    let mut entries = std::fs::read_dir(path)?.filter_map(|e| e.ok()).collect::<Vec<_>>();
+    // skip hidden files if not showing them, dotfiles smh
     if !show_hidden {
         entries.retain(|e| !e.file_name().to_string_lossy().starts_with('.'));
     }
@@ -453,80 +2153,166 @@ fn get_entries(path: &Path, show_hidden: bool, filter: Option<&str>) -> io::Resu
     Ok(entries)
 }
 
-fn get_icon_for_entry(entry: &std::fs::DirEntry) -> String {
-    let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-    if is_dir {
-        "[DIR] ".to_string()
+// the few colored colors the git markers use, in ratatui terms
+fn tui_color(color: Color) -> TuiColor {
+    match color {
+        Color::Red => TuiColor::Red,
+        Color::Green => TuiColor::Green,
+        Color::Yellow => TuiColor::Yellow,
+        Color::Cyan => TuiColor::Cyan,
+        _ => TuiColor::DarkGray,
+    }
+}
+
+/// --detect found an elf, pe or mach-o header
+fn detected_program(node: &TreeNode) -> bool {
+    node.detected.as_deref().and_then(icons::kind_for_detected).is_some_and(|k| k.category == icons::Category::Executable)
+}
+
+fn get_color_for_entry(entry: &TuiEntry) -> TuiColor {
+    if entry.node.error.is_some() {
+        TuiColor::Red
+    } else if entry.node.name.starts_with('.') {
+        TuiColor::Gray
+    } else if entry.node.is_dir {
+        TuiColor::Blue
+    } else if entry.executable || detected_program(&entry.node) {
+        TuiColor::Green
     } else {
-        if let Ok(metadata) = entry.metadata() {
-            if metadata.len() > 1024 * 1024 {
-                return "[BIG] ".to_string();
+        TuiColor::White
+    }
+}
+
+// what the tui browses: the live disk, or a tree loaded with --from-json
+enum TuiSource<'a> {
+    Disk {
+        show_hidden: bool,
+        filter: Option<&'a str>,
+        loc: bool,
+        // --loc counts by path, a folder's count covers everything below it so going in costs nothing
+        loc_cache: RefCell<HashMap<PathBuf, Option<loc::Loc>>>,
+        detect: bool,
+        types: Option<&'a [String]>,
+        git: Option<&'a git::GitStatus>,
+    },
+    Snapshot(&'a TreeNode),
+}
+
+struct TuiEntry {
+    node: TreeNode,
+    executable: bool,
+}
+
+impl TuiSource<'_> {
+    /// entries of the folder `trail` names below the root, without their children
+    fn list(&self, root: &Path, trail: &[String]) -> io::Result<Vec<TuiEntry>> {
+        match self {
+            TuiSource::Disk { show_hidden, filter, loc, loc_cache, detect, types, git } => {
+                let dir = trail.iter().fold(root.to_path_buf(), |p, name| p.join(name));
+                // a folder that went away or locked up since it was listed just shows empty
+                let entries = get_entries(&dir, *show_hidden, *filter).unwrap_or_default();
+                Ok(entries
+                    .iter()
+                    .map(|e| {
+                        let mut node = leaf_node(e, false);
+                        // opening is cheap next to listing, and it shows which folders can't be entered
+                        if let (true, Err(err)) = (node.is_dir, std::fs::read_dir(e.path())) {
+                            node.error = Some(ScanError::from_io(&err));
+                        }
+                        // folders are counted when they're listed, only as deep as the user looks
+                        if *loc {
+                            let path = e.path();
+                            let cached = loc_cache.borrow().get(&path).copied();
+                            node.loc = match cached {
+                                Some(counts) => counts,
+                                None if node.is_dir => match build_tree(&path, None, *show_hidden, *filter, false) {
+                                    Ok(mut t) => {
+                                        loc::fill_loc(&mut t, &path);
+                                        remember_loc(&t, &path, &mut loc_cache.borrow_mut());
+                                        t.loc
+                                    }
+                                    Err(_) => None,
+                                },
+                                None if node.special.is_none() => loc::count_file(&path, &node.name),
+                                None => None,
+                            };
+                        }
+                        if *detect && !node.is_dir && node.special.is_none() {
+                            node.detected = detect::sniff(&e.path()).map(str::to_string);
+                        }
+                        if let Some(status) = git {
+                            let rel = trail.iter().chain([&node.name]).cloned().collect::<Vec<_>>().join("/");
+                            node.git = Some(status.state(&rel, node.is_dir));
+                        }
+                        TuiEntry { node, executable: is_executable(&e.path()) }
+                    })
+                    .filter(|e| {
+                        e.node.is_dir
+                            || types.is_none_or(|types| types.iter().any(|t| icons::type_matches(&e.node.name, e.node.detected.as_deref(), t)))
+                    })
+                    .collect())
             }
-        }
-        let path = entry.path();
-        if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-            match ext_str.as_str() {
-                "py" => "[.py] ".to_string(),
-                "rs" => "[.rs] ".to_string(),
-                "js" => "[.js] ".to_string(),
-                "ts" => "[.ts] ".to_string(),
-                "html" => "[.html] ".to_string(),
-                "css" => "[.css] ".to_string(),
-                "md" => "[.md] ".to_string(),
-                "txt" => "[.txt] ".to_string(),
-                "exe" | "bat" | "cmd" | "sh" => "[EXEC] ".to_string(),
-                _ => format!("[{}] ", ext_str),
+            TuiSource::Snapshot(tree) => {
+                let mut node = *tree;
+                for name in trail {
+                    match node.children.iter().find(|c| c.is_dir && &c.name == name) {
+                        Some(child) => node = child,
+                        None => return Ok(Vec::new()),
+                    }
+                }
+                Ok(node
+                    .children
+                    .iter()
+                    .map(|c| TuiEntry {
+                        node: TreeNode { children: Vec::new(), ..c.clone() },
+                        executable: !c.is_dir && c.mode.is_some_and(|m| m & 0o111 != 0),
+                    })
+                    .collect())
             }
-        } else {
-            "[FILE] ".to_string()
         }
     }
 }
 
-fn get_color_for_entry(entry: &std::fs::DirEntry) -> TuiColor {
-    let file_name = entry.file_name();
-    let file_name_str = file_name.to_string_lossy();
-    if file_name_str.starts_with('.') {
-        TuiColor::Gray
-    } else {
-        let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-        if is_dir {
-            TuiColor::Blue
-        } else if is_executable(&entry.path()) {
-            TuiColor::Green
-        } else {
-            TuiColor::White
-        }
+fn remember_loc(node: &TreeNode, path: &Path, cache: &mut HashMap<PathBuf, Option<loc::Loc>>) {
+    cache.insert(path.to_path_buf(), node.loc);
+    for child in &node.children {
+        remember_loc(child, &path.join(&child.name), cache);
     }
 }
 
-fn run_tui(path: &Path, show_hidden: bool, filter: Option<&str>) -> io::Result<()> {
+fn run_tui(path: &Path, source: TuiSource, icons: &IconSet) -> io::Result<()> {
     let mut stdout = io::stdout();
     crossterm::execute!(stdout, EnableMouseCapture, crossterm::terminal::EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut current_path = path.to_path_buf();
+    // folder names below `path`, the way back up is just a pop
+    let mut trail: Vec<String> = Vec::new();
     let mut selected = 0;
-    let mut entries = get_entries(&current_path, show_hidden, filter)?;
+    let mut entries = source.list(path, &trail)?;
 
     let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| -> io::Result<()> {
         loop {
             terminal.draw(|f| {
                 let size = f.size();
                 let items: Vec<ListItem> = entries.iter().map(|e| {
-                    let icon = get_icon_for_entry(e);
                     let color = get_color_for_entry(e);
-                    let name = e.file_name().to_string_lossy().to_string();
-                    ListItem::new(Line::from(vec![
-                        Span::styled(icon, Style::default().fg(color)),
-                        Span::styled(name, Style::default()),
-                    ]))
+                    let icon = icons.icon(&e.node.name, e.node.is_dir, e.node.size, e.node.detected.as_deref());
+                    let mut spans = Vec::new();
+                    if let Some(state) = e.node.git {
+                        let marker = if state == git::GitState::Clean { " " } else { state.marker() };
+                        spans.push(Span::styled(format!("{} ", marker), Style::default().fg(tui_color(state.color()))));
+                    }
+                    spans.push(Span::styled(icon, Style::default().fg(color)));
+                    spans.push(Span::styled(e.node.name.clone(), Style::default()));
+                    if let Some(loc) = &e.node.loc {
+                        spans.push(Span::styled(format!("  {} loc", loc.code), Style::default().fg(TuiColor::DarkGray)));
+                    }
+                    ListItem::new(Line::from(spans))
                 }).collect();
 
                 let list = List::new(items)
-                    .block(Block::default().borders(Borders::ALL).title(format!("{} ({} items)", current_path.display(), entries.len())))
+                    .block(Block::default().borders(Borders::ALL).title(format!("{} ({} items)", trail.iter().fold(path.to_path_buf(), |p, n| p.join(n)).display(), entries.len())))
                     .highlight_style(Style::default().add_modifier(Modifier::BOLD))
                     .highlight_symbol(">> ");
 
@@ -540,42 +2326,36 @@ fn run_tui(path: &Path, show_hidden: bool, filter: Option<&str>) -> io::Result<(
                 if key.kind == KeyEventKind::Press {
                     match key.code {
                         KeyCode::Up => {
-                            if selected > 0 {
-                                selected -= 1;
-                            }
+                            selected = selected.saturating_sub(1);
                         }
-                        KeyCode::Down => {
-                            if selected < entries.len().saturating_sub(1) {
-                                selected += 1;
-                            }
+                        KeyCode::Down if selected < entries.len().saturating_sub(1) => {
+                            selected += 1;
                         }
                         KeyCode::Right => {
                             if let Some(entry) = entries.get(selected) {
-                                let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-                                if is_dir {
-                                    current_path.push(entry.file_name());
-                                    entries = get_entries(&current_path, show_hidden, filter)?;
+                                if entry.node.is_dir && entry.node.error.is_none() {
+                                    trail.push(entry.node.name.clone());
+                                    entries = source.list(path, &trail)?;
                                     selected = 0;
                                 }
                             }
                         }
-                        KeyCode::Enter => {
+                        // a snapshot's files aren't on this machine, nothing to open
+                        KeyCode::Enter if matches!(source, TuiSource::Disk { .. }) => {
                             if let Some(entry) = entries.get(selected) {
-                                let path_str = entry.path().to_string_lossy().to_string();
-                                let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
-                                if is_dir {
+                                let full = trail.iter().fold(path.to_path_buf(), |p, n| p.join(n)).join(&entry.node.name);
+                                let path_str = full.to_string_lossy().to_string();
+                                if entry.node.is_dir {
                                     std::process::Command::new("explorer").arg(&path_str).spawn().ok();
                                 } else {
-                                    std::process::Command::new("cmd").args(&["/c", "start", "", &path_str]).spawn().ok();
+                                    std::process::Command::new("cmd").args(["/c", "start", "", &path_str]).spawn().ok();
                                 }
                             }
                         }
-                        KeyCode::Left | KeyCode::Backspace | KeyCode::Esc => {
-                            if current_path != path {
-                                current_path.pop();
-                                entries = get_entries(&current_path, show_hidden, filter)?;
-                                selected = 0;
-                            }
+                        KeyCode::Left | KeyCode::Backspace | KeyCode::Esc if !trail.is_empty() => {
+                            trail.pop();
+                            entries = source.list(path, &trail)?;
+                            selected = 0;
                         }
                         KeyCode::Char('q') => break,
                         _ => {}
@@ -593,6 +2373,45 @@ fn run_tui(path: &Path, show_hidden: bool, filter: Option<&str>) -> io::Result<(
     )?;
     match res {
         Ok(inner) => inner,
-        Err(_) => Err(io::Error::new(io::ErrorKind::Other, "TUI Panicked!!!")),
+        Err(_) => Err(io::Error::other("TUI Panicked!!!")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_size_units() {
+        assert_eq!(format_size(512, false), "512B");
+        assert_eq!(format_size(1536, false), "1.5K");
+        assert_eq!(format_size(3 * 1024 * 1024, false), "3.0M");
+        assert_eq!(format_size(999, true), "999B");
+        assert_eq!(format_size(1500, true), "1.5kB");
+        assert_eq!(format_size(2_000_000, true), "2.0MB");
+    }
+
+    #[test]
+    fn parse_size_suffixes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("10K"), Some(10 * 1024));
+        assert_eq!(parse_size("1.5M"), Some(1536 * 1024));
+        assert_eq!(parse_size("2GiB"), Some(2 << 30));
+        assert_eq!(parse_size("10KB"), Some(10_000));
+        assert_eq!(parse_size("1.5kB"), Some(1500));
+        assert_eq!(parse_size(" 3 mb "), Some(3_000_000));
+        assert_eq!(parse_size("12B"), Some(12));
+        assert_eq!(parse_size("1µ"), None);
+        assert_eq!(parse_size("10Q"), None);
+        assert_eq!(parse_size("big"), None);
+    }
+
+    #[test]
+    fn si_sizes_read_back() {
+        for bytes in [1500, 2_000_000, 7_300_000_000] {
+            let text = format_size(bytes, true);
+            let back = parse_size(&text).unwrap();
+            assert!(back.abs_diff(bytes) * 20 < bytes, "{} -> {} -> {}", bytes, text, back);
+        }
     }
 }
@@ -0,0 +1,253 @@
+use crate::hash::{hash_file, split_tagged, HashAlgo};
+use crate::{join_path, Special, TreeNode};
+use colored::Colorize;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+// canopy manifest create / verify: checksums for release artifacts and backup restores
+
+#[derive(Serialize, Deserialize)]
+struct JsonManifest {
+    algorithm: String,
+    files: Vec<JsonEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonEntry {
+    path: String,
+    size: u64,
+    hash: String,
+}
+
+/// every hashed file in the tree by its path below the root
+pub fn hashed_files(node: &TreeNode, path: &str, out: &mut BTreeMap<String, (u64, String)>) {
+    for child in &node.children {
+        let child_path = join_path(path, &child.name);
+        if child.is_dir {
+            hashed_files(child, &child_path, out);
+        } else if let Some((_, hex)) = child.hash.as_deref().and_then(split_tagged) {
+            out.insert(child_path, (child.size.unwrap_or(0), hex.to_string()));
+        }
+    }
+}
+
+/// `sums` is the `sha256sum` / `b3sum` line format, `json` has sizes and the algorithm in it too
+pub fn write_manifest<W: Write>(out: &mut W, files: &BTreeMap<String, (u64, String)>, algo: HashAlgo, json: bool) -> io::Result<()> {
+    if json {
+        let manifest = JsonManifest {
+            algorithm: algo.name().to_string(),
+            files: files
+                .iter()
+                .map(|(path, (size, hash))| JsonEntry { path: path.clone(), size: *size, hash: hash.clone() })
+                .collect(),
+        };
+        serde_json::to_writer_pretty(&mut *out, &manifest)?;
+        writeln!(out)?;
+    } else {
+        for (path, (_, hash)) in files {
+            // two spaces is text mode in sha256sum, which is what -c expects by default
+            match escape_name(path) {
+                Some(escaped) => writeln!(out, "\\{}  {}", hash, escaped)?,
+                None => writeln!(out, "{}  {}", hash, path)?,
+            }
+        }
+    }
+    out.flush()
+}
+
+// sha256sum's way of keeping one file per line: a `\` in front of the line says the name has
+// `\\`, `\n` and `\r` in it for backslashes, newlines and carriage returns
+fn escape_name(name: &str) -> Option<String> {
+    if !name.contains(['\\', '\n', '\r']) {
+        return None;
+    }
+    Some(name.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r"))
+}
+
+fn unescape_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('\\')) => out.push('\\'),
+            ('\\', Some('n')) => out.push('\n'),
+            ('\\', Some('r')) => out.push('\r'),
+            _ => {
+                out.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    out
+}
+
+/// the manifest's files and the algorithm they were hashed with. text manifests don't say, so
+/// without `algo` 16 hex digits means xxh3 and anything else sha256
+pub fn read_manifest(text: &str, algo: Option<HashAlgo>) -> Result<(HashAlgo, BTreeMap<String, String>), String> {
+    if text.trim_start().starts_with('{') {
+        let manifest: JsonManifest = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let algo = HashAlgo::parse(&manifest.algorithm).ok_or_else(|| format!("unknown algorithm {}", manifest.algorithm))?;
+        return Ok((algo, manifest.files.into_iter().map(|e| (e.path, e.hash)).collect()));
+    }
+    let mut files = BTreeMap::new();
+    let mut short = false;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (escaped, line) = match line.strip_prefix('\\') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        // `hash  path` in text mode, `hash *path` in binary mode
+        let (hash, rest) = line
+            .split_once(' ')
+            .ok_or_else(|| format!("line {}: expected `<hash>  <path>`", i + 1))?;
+        let path = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*')).unwrap_or(rest);
+        let path = if escaped { unescape_name(path) } else { path.to_string() };
+        short |= hash.len() == 16;
+        files.insert(path.trim_start_matches("./").to_string(), hash.to_lowercase());
+    }
+    let algo = algo.unwrap_or(if short { HashAlgo::Xxh3 } else { HashAlgo::Sha256 });
+    Ok((algo, files))
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub ok: usize,
+    pub missing: Vec<String>,
+    pub corrupted: Vec<String>,
+    pub extra: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+/// check every manifest entry against `root`. `on_disk` is the walked tree, for spotting extra files
+pub fn verify(root: &Path, on_disk: &TreeNode, expected: &BTreeMap<String, String>, algo: HashAlgo, max_size: Option<u64>) -> Report {
+    let mut disk = BTreeSet::new();
+    disk_files(on_disk, "", max_size, &mut disk);
+
+    enum Outcome {
+        Ok,
+        Missing,
+        Corrupted,
+        Skipped,
+    }
+    let outcomes: Vec<(&String, Outcome)> = expected
+        .par_iter()
+        .map(|(path, want)| {
+            let full = root.join(path);
+            let outcome = match std::fs::metadata(&full) {
+                Err(_) => Outcome::Missing,
+                Ok(md) if !md.is_file() => Outcome::Missing,
+                Ok(md) if max_size.is_some_and(|max| md.len() > max) => Outcome::Skipped,
+                Ok(_) => match hash_file(&full, algo) {
+                    Ok(got) if got.eq_ignore_ascii_case(want) => Outcome::Ok,
+                    // unreadable counts as corrupted, either way the file can't be trusted
+                    _ => Outcome::Corrupted,
+                },
+            };
+            (path, outcome)
+        })
+        .collect();
+
+    let mut report = Report::default();
+    for (path, outcome) in outcomes {
+        match outcome {
+            Outcome::Ok => report.ok += 1,
+            Outcome::Missing => report.missing.push(path.clone()),
+            Outcome::Corrupted => report.corrupted.push(path.clone()),
+            Outcome::Skipped => report.skipped.push(path.clone()),
+        }
+    }
+    report.extra = disk.into_iter().filter(|p| !expected.contains_key(p)).collect();
+    report
+}
+
+// files over the size limit were left out when the manifest was made, so they don't count as extra
+fn disk_files(node: &TreeNode, path: &str, max_size: Option<u64>, out: &mut BTreeSet<String>) {
+    for child in &node.children {
+        let child_path = join_path(path, &child.name);
+        if child.is_dir {
+            disk_files(child, &child_path, max_size, out);
+        } else if !child.special.is_some_and(Special::is_stream) && max_size.is_none_or(|max| child.size.unwrap_or(0) <= max) {
+            out.insert(child_path);
+        }
+    }
+}
+
+pub fn print_report(report: &Report, ignore_extra: bool) {
+    for path in &report.missing {
+        println!("{} {}", "MISSING".red().bold(), path);
+    }
+    for path in &report.corrupted {
+        println!("{} {}", "CORRUPT".red().bold(), path);
+    }
+    if !ignore_extra {
+        for path in &report.extra {
+            println!("{}   {}", "EXTRA".yellow().bold(), path);
+        }
+    }
+    for path in &report.skipped {
+        println!("{} {}", "SKIPPED".dimmed(), path.dimmed());
+    }
+    let checked = report.ok + report.missing.len() + report.corrupted.len();
+    println!(
+        "{} files checked: {} ok, {} missing, {} corrupted, {} extra{}",
+        checked,
+        report.ok.to_string().green(),
+        report.missing.len(),
+        report.corrupted.len(),
+        report.extra.len(),
+        if report.skipped.is_empty() { String::new() } else { format!(", {} skipped", report.skipped.len()) }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sums(files: &[(&str, &str)]) -> String {
+        let files = files.iter().map(|(p, h)| (p.to_string(), (0, h.to_string()))).collect();
+        let mut out = Vec::new();
+        write_manifest(&mut out, &files, HashAlgo::Sha256, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn names_with_backslashes_and_newlines_are_escaped_like_sha256sum() {
+        let hash = "ab".repeat(32);
+        let text = sums(&[("plain.txt", &hash), ("back\\slash", &hash), ("two\nlines", &hash)]);
+        assert_eq!(text, format!("\\{h}  back\\\\slash\n{h}  plain.txt\n\\{h}  two\\nlines\n", h = hash));
+        let (_, files) = read_manifest(&text, None).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["back\\slash", "plain.txt", "two\nlines"]);
+    }
+
+    #[test]
+    fn binary_mode_and_dot_slash() {
+        let hash = "cd".repeat(32);
+        let (_, files) = read_manifest(&format!("{}  ./a/b.txt\n{} *c.bin\n", hash, hash.to_uppercase()), None).unwrap();
+        assert_eq!(files["a/b.txt"], hash);
+        assert_eq!(files["c.bin"], hash);
+    }
+
+    #[test]
+    fn algorithm_is_only_guessed_when_not_given() {
+        let text = "0123456789abcdef  a.txt\n";
+        assert_eq!(read_manifest(text, None).unwrap().0, HashAlgo::Xxh3);
+        assert_eq!(read_manifest(text, Some(HashAlgo::Blake3)).unwrap().0, HashAlgo::Blake3);
+        assert_eq!(read_manifest(&format!("{}  a.txt\n", "0".repeat(64)), None).unwrap().0, HashAlgo::Sha256);
+    }
+
+    #[test]
+    fn json_manifests_name_their_algorithm() {
+        let text = r#"{"algorithm": "blake3", "files": [{"path": "a", "size": 1, "hash": "ff"}]}"#;
+        let (algo, files) = read_manifest(text, Some(HashAlgo::Sha256)).unwrap();
+        assert_eq!(algo, HashAlgo::Blake3);
+        assert_eq!(files["a"], "ff");
+    }
+}
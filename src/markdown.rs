@@ -0,0 +1,68 @@
+use crate::{join_path, TreeNode};
+
+// markdown for READMEs and design docs, the fenced style lives in main next to
+// print_tree since it's the same drawing, this is the bullet list one
+
+pub fn print_list(tree: &TreeNode, root_label: &str, link_base: Option<&str>) {
+    println!("- {}", entry(root_label, true, None, tree.note.as_deref()));
+    for child in &tree.children {
+        print_item(child, "", 1, link_base);
+    }
+}
+
+fn print_item(node: &TreeNode, parent: &str, depth: usize, link_base: Option<&str>) {
+    let path = join_path(parent, &node.name);
+    let link = link_base.map(|base| link_for(base, &path));
+    println!("{}- {}", "  ".repeat(depth), entry(&node.name, node.is_dir, link.as_deref(), node.note.as_deref()));
+    for child in &node.children {
+        print_item(child, &path, depth + 1, link_base);
+    }
+}
+
+fn entry(name: &str, is_dir: bool, link: Option<&str>, note: Option<&str>) -> String {
+    let label = if is_dir {
+        format!("{}/", escape(name))
+    } else {
+        escape(name)
+    };
+    let mut out = match link {
+        Some(url) if is_dir => format!("[**{}**]({})", label, url),
+        Some(url) => format!("[{}]({})", label, url),
+        None if is_dir => format!("**{}**", label),
+        None => label,
+    };
+    if let Some(note) = note {
+        out.push_str(" — ");
+        out.push_str(note);
+    }
+    out
+}
+
+fn link_for(base: &str, path: &str) -> String {
+    let encoded: String = path
+        .chars()
+        .map(|c| match c {
+            ' ' => "%20".to_string(),
+            '(' => "%28".to_string(),
+            ')' => "%29".to_string(),
+            _ => c.to_string(),
+        })
+        .collect();
+    if base.is_empty() {
+        encoded
+    } else {
+        format!("{}/{}", base.trim_end_matches('/'), encoded)
+    }
+}
+
+// keep names like __init__.py or *.rs from turning into bold/italics
+fn escape(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '*' | '_' | '`' | '[' | ']' | '<' | '>' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,133 @@
+use crate::TreeNode;
+use std::fs::Metadata;
+use std::time::UNIX_EPOCH;
+
+// extra per-entry metadata, only collected when something actually shows it (-l, exports)
+
+pub fn fill_metadata(node: &mut TreeNode, md: &Metadata) {
+    node.mtime = md
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64);
+    fill_platform(node, md);
+}
+
+#[cfg(unix)]
+fn fill_platform(node: &mut TreeNode, md: &Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    node.mode = Some(md.mode());
+    node.owner = Some(user_name(md.uid()));
+    node.group = Some(group_name(md.gid()));
+}
+
+#[cfg(windows)]
+fn fill_platform(node: &mut TreeNode, md: &Metadata) {
+    // no real unix modes on windows, fake something close so the column still means something
+    let kind = if md.is_dir() { 0o040000 } else { 0o100000 };
+    let perms = if md.permissions().readonly() { 0o444 } else { 0o644 };
+    node.mode = Some(kind | perms);
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> String {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    thread_local! {
+        static CACHE: RefCell<HashMap<u32, String>> = RefCell::new(HashMap::new());
+    }
+    CACHE.with(|c| {
+        c.borrow_mut()
+            .entry(uid)
+            .or_insert_with(|| {
+                uzers::get_user_by_uid(uid)
+                    .map(|u| u.name().to_string_lossy().to_string())
+                    .unwrap_or_else(|| uid.to_string())
+            })
+            .clone()
+    })
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> String {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    thread_local! {
+        static CACHE: RefCell<HashMap<u32, String>> = RefCell::new(HashMap::new());
+    }
+    CACHE.with(|c| {
+        c.borrow_mut()
+            .entry(gid)
+            .or_insert_with(|| {
+                uzers::get_group_by_gid(gid)
+                    .map(|g| g.name().to_string_lossy().to_string())
+                    .unwrap_or_else(|| gid.to_string())
+            })
+            .clone()
+    })
+}
+
+/// `drwxr-xr-x` style string, like ls prints
+pub fn format_mode(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o010000 => 'p',
+        0o140000 => 's',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        _ => '-',
+    };
+    let mut s = String::with_capacity(10);
+    s.push(kind);
+    let bits = [
+        (0o400, 'r'), (0o200, 'w'), (0o100, 'x'),
+        (0o040, 'r'), (0o020, 'w'), (0o010, 'x'),
+        (0o004, 'r'), (0o002, 'w'), (0o001, 'x'),
+    ];
+    for (bit, ch) in bits {
+        s.push(if mode & bit != 0 { ch } else { '-' });
+    }
+    // setuid/setgid/sticky go where the x would be
+    let mut chars: Vec<char> = s.chars().collect();
+    if mode & 0o4000 != 0 {
+        chars[3] = if chars[3] == 'x' { 's' } else { 'S' };
+    }
+    if mode & 0o2000 != 0 {
+        chars[6] = if chars[6] == 'x' { 's' } else { 'S' };
+    }
+    if mode & 0o1000 != 0 {
+        chars[9] = if chars[9] == 'x' { 't' } else { 'T' };
+    }
+    chars.into_iter().collect()
+}
+
+pub fn format_time_iso(secs: i64) -> String {
+    use chrono::{Local, TimeZone};
+    match Local.timestamp_opt(secs, 0).single() {
+        Some(t) => t.format("%Y-%m-%d %H:%M").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+pub fn format_time_relative(secs: i64, now: i64) -> String {
+    let diff = now - secs;
+    if diff < 0 {
+        return "in the future".to_string();
+    }
+    let (n, unit) = if diff < 60 {
+        return "just now".to_string();
+    } else if diff < 3600 {
+        (diff / 60, "min")
+    } else if diff < 86400 {
+        (diff / 3600, "hour")
+    } else if diff < 86400 * 30 {
+        (diff / 86400, "day")
+    } else if diff < 86400 * 365 {
+        (diff / (86400 * 30), "month")
+    } else {
+        (diff / (86400 * 365), "year")
+    };
+    let plural = if n == 1 || unit == "min" { "" } else { "s" };
+    format!("{} {}{} ago", n, unit, plural)
+}
@@ -0,0 +1,61 @@
+use crate::{join_path, TreeNode};
+use std::collections::HashMap;
+use std::path::Path;
+
+// sidecar annotations, one entry per line, same shape as the docs we paste trees into:
+//
+//   src/           # core library
+//   src/main.rs    # cli entry point
+//
+// paths are relative to the scanned folder, `.` is the folder itself
+
+pub fn load_annotations(path: &Path) -> std::io::Result<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path)?;
+    let mut notes = HashMap::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // the comment starts at the first # that follows whitespace, so names with # in them still work
+        let split = line.char_indices().find(|&(i, c)| c == '#' && i > 0 && line[..i].ends_with(char::is_whitespace));
+        let Some((at, _)) = split else {
+            continue;
+        };
+        let key = normalize(line[..at].trim());
+        let note = line[at + 1..].trim();
+        if !note.is_empty() {
+            notes.insert(key, note.to_string());
+        }
+    }
+    Ok(notes)
+}
+
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = path.trim_start_matches("./").trim_end_matches('/');
+    if path.is_empty() {
+        ".".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+pub fn apply_annotations(tree: &mut TreeNode, notes: &HashMap<String, String>) {
+    if let Some(note) = notes.get(".") {
+        tree.note = Some(note.clone());
+    }
+    for child in &mut tree.children {
+        apply_at(child, "", notes);
+    }
+}
+
+fn apply_at(node: &mut TreeNode, parent: &str, notes: &HashMap<String, String>) {
+    let path = join_path(parent, &node.name);
+    if let Some(note) = notes.get(&path) {
+        node.note = Some(note.clone());
+    }
+    for child in &mut node.children {
+        apply_at(child, &path, notes);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::{format_size, join_path, parse_size, TreeNode};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+// canopy check: layout rules for a repository, the kind of thing code review keeps catching by hand.
+//
+//   ignore = ["target", ".git"]
+//
+//   [[required]]
+//   paths = ["crates/*"]     # folders, the root when left out
+//   files = ["README.md"]
+//
+//   [[forbidden]]
+//   paths = ["src/**/*.orig"]
+//   message = "leftover from a merge"
+//
+//   [[max_size]]
+//   size = "5M"
+//
+//   [[max_depth]]
+//   depth = 8
+//
+// patterns with a `/` match the whole path below the root, without one they match the name at any depth.
+// every rule can have an `id`, a `message` and `level = "warning"` so it's reported but doesn't fail the check
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RuleFile {
+    ignore: Vec<String>,
+    required: Vec<RuleSpec>,
+    forbidden: Vec<RuleSpec>,
+    max_size: Vec<RuleSpec>,
+    max_depth: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RuleSpec {
+    id: Option<String>,
+    message: Option<String>,
+    level: Level,
+    paths: Vec<String>,
+    /// required only
+    files: Vec<String>,
+    /// max_size only
+    size: Option<String>,
+    /// max_depth only
+    depth: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    #[default]
+    Error,
+    Warning,
+}
+
+pub struct Rules {
+    ignore: Vec<PathPattern>,
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    id: String,
+    level: Level,
+    message: Option<String>,
+    paths: Vec<PathPattern>,
+    kind: RuleKind,
+}
+
+enum RuleKind {
+    Required(Vec<PathPattern>),
+    Forbidden,
+    MaxSize(u64),
+    MaxDepth(usize),
+}
+
+struct PathPattern {
+    glob: glob::Pattern,
+    whole_path: bool,
+}
+
+impl PathPattern {
+    fn new(text: &str) -> Result<PathPattern, String> {
+        let text = text.trim_start_matches("./").trim_end_matches('/');
+        let glob = glob::Pattern::new(text).map_err(|e| format!("invalid pattern {}: {}", text, e))?;
+        Ok(PathPattern { glob, whole_path: text.contains('/') })
+    }
+
+    fn matches(&self, rel: &str, name: &str) -> bool {
+        // `*` stays inside one folder, `**` goes through any number of them
+        let opts = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+        // `.` is the checked folder itself
+        if matches!(self.glob.as_str(), "." | "") {
+            return rel.is_empty();
+        }
+        match self.whole_path {
+            true => self.glob.matches_with(rel, opts),
+            false => self.glob.matches_with(name, opts),
+        }
+    }
+}
+
+pub fn load_rules(text: &str) -> Result<Rules, String> {
+    let file: RuleFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    let patterns = |list: &[String]| list.iter().map(|p| PathPattern::new(p)).collect::<Result<Vec<_>, _>>();
+    let mut rules = Vec::new();
+    let kinds = [("required", &file.required), ("forbidden", &file.forbidden), ("max_size", &file.max_size), ("max_depth", &file.max_depth)];
+    for (kind_name, specs) in kinds {
+        for (i, spec) in specs.iter().enumerate() {
+            let id = spec.id.clone().unwrap_or_else(|| format!("{}-{}", kind_name, i + 1));
+            let kind = match kind_name {
+                "required" if spec.files.is_empty() => return Err(format!("{}: required rules need `files`", id)),
+                "required" => RuleKind::Required(patterns(&spec.files)?),
+                "forbidden" if spec.paths.is_empty() => return Err(format!("{}: forbidden rules need `paths`", id)),
+                "forbidden" => RuleKind::Forbidden,
+                "max_size" => {
+                    let size = spec.size.as_deref().ok_or_else(|| format!("{}: max_size rules need `size`", id))?;
+                    RuleKind::MaxSize(parse_size(size).ok_or_else(|| format!("{}: invalid size {}", id, size))?)
+                }
+                _ => RuleKind::MaxDepth(spec.depth.ok_or_else(|| format!("{}: max_depth rules need `depth`", id))?),
+            };
+            rules.push(Rule { id, level: spec.level, message: spec.message.clone(), paths: patterns(&spec.paths)?, kind });
+        }
+    }
+    Ok(Rules { ignore: patterns(&file.ignore)?, rules })
+}
+
+#[derive(Serialize)]
+pub struct Violation {
+    pub rule: String,
+    pub level: Level,
+    /// below the checked folder, `.` for the folder itself
+    pub path: String,
+    pub message: String,
+}
+
+pub struct Report {
+    pub violations: Vec<Violation>,
+    /// entries looked at, ignored ones don't count
+    pub checked: usize,
+}
+
+impl Report {
+    pub fn count(&self, level: Level) -> usize {
+        self.violations.iter().filter(|v| v.level == level).count()
+    }
+}
+
+struct Entry<'a> {
+    rel: String,
+    depth: usize,
+    node: &'a TreeNode,
+}
+
+pub fn check(tree: &TreeNode, rules: &Rules) -> Report {
+    let mut entries = vec![Entry { rel: String::new(), depth: 0, node: tree }];
+    collect(tree, "", 1, &rules.ignore, &mut entries);
+    let mut violations = Vec::new();
+    for rule in &rules.rules {
+        let applies = |e: &Entry| match rule.paths.is_empty() {
+            true => true,
+            false => rule.paths.iter().any(|p| p.matches(&e.rel, &e.node.name)),
+        };
+        let mut report = |rel: &str, text: String| {
+            let message = match &rule.message {
+                Some(m) => format!("{} ({})", text, m),
+                None => text,
+            };
+            let path = if rel.is_empty() { ".".to_string() } else { rel.to_string() };
+            violations.push(Violation { rule: rule.id.clone(), level: rule.level, path, message });
+        };
+        match &rule.kind {
+            RuleKind::Required(files) => {
+                // no paths means the root folder
+                let folders = entries.iter().filter(|e| e.node.is_dir).filter(|e| if rule.paths.is_empty() { e.depth == 0 } else { applies(e) });
+                for folder in folders {
+                    for file in files.iter().filter(|f| !contains(folder.node, "", f)) {
+                        report(&folder.rel, format!("missing {}", file.glob.as_str()));
+                    }
+                }
+            }
+            RuleKind::Forbidden => {
+                // a forbidden folder is reported once, not once more for everything in it.
+                // entries are in walk order, so what's inside a folder comes right after it
+                let mut reported: Option<String> = None;
+                for entry in entries.iter().skip(1).filter(|e| applies(e)) {
+                    if reported.as_ref().is_some_and(|r| entry.rel.starts_with(r.as_str())) {
+                        continue;
+                    }
+                    report(&entry.rel, "not allowed here".to_string());
+                    reported = Some(format!("{}/", entry.rel));
+                }
+            }
+            RuleKind::MaxSize(limit) => {
+                for entry in entries.iter().filter(|e| !e.node.is_dir && applies(e)) {
+                    if let Some(size) = entry.node.size.filter(|s| s > limit) {
+                        report(&entry.rel, format!("{} is over the {} limit", format_size(size, false), format_size(*limit, false)));
+                    }
+                }
+            }
+            RuleKind::MaxDepth(limit) => {
+                // only the first level past the limit, everything below it is too deep for the same reason
+                for entry in entries.iter().filter(|e| e.depth == limit + 1 && applies(e)) {
+                    report(&entry.rel, format!("{} levels deep, the limit is {}", entry.depth, limit));
+                }
+            }
+        }
+    }
+    Report { checked: entries.len() - 1, violations }
+}
+
+// `README.md` has to be right in the folder, `src/lib.rs` can be further down
+fn contains(node: &TreeNode, parent: &str, file: &PathPattern) -> bool {
+    node.children.iter().any(|c| {
+        let rel = join_path(parent, &c.name);
+        file.matches(&rel, &c.name) || (file.whole_path && c.is_dir && contains(c, &rel, file))
+    })
+}
+
+fn collect<'a>(node: &'a TreeNode, parent: &str, depth: usize, ignore: &[PathPattern], out: &mut Vec<Entry<'a>>) {
+    for child in &node.children {
+        let rel = join_path(parent, &child.name);
+        if ignore.iter().any(|p| p.matches(&rel, &child.name)) {
+            continue;
+        }
+        out.push(Entry { rel: rel.clone(), depth, node: child });
+        collect(child, &rel, depth + 1, ignore, out);
+    }
+}
+
+pub fn print_report(report: &Report) {
+    for v in &report.violations {
+        let level = match v.level {
+            Level::Error => "error".red().bold(),
+            Level::Warning => "warning".yellow().bold(),
+        };
+        println!("{}[{}] {}: {}", level, v.rule, v.path.bold(), v.message);
+    }
+    if !report.violations.is_empty() {
+        println!();
+    }
+    let (errors, warnings) = (report.count(Level::Error), report.count(Level::Warning));
+    if errors + warnings == 0 {
+        println!("{} entries checked, no violations", report.checked);
+    } else {
+        println!(
+            "{} errors, {} warnings in {} entries",
+            errors.to_string().red(),
+            warnings.to_string().yellow(),
+            report.checked
+        );
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    checked: usize,
+    errors: usize,
+    warnings: usize,
+    violations: &'a [Violation],
+}
+
+pub fn export_json(report: &Report) -> io::Result<()> {
+    let out = JsonReport {
+        checked: report.checked,
+        errors: report.count(Level::Error),
+        warnings: report.count(Level::Warning),
+        violations: &report.violations,
+    };
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &out)?;
+    writeln!(stdout)
+}
+
+// sarif 2.1.0, what github code scanning and most ci dashboards take in. just the parts we fill
+
+#[derive(Serialize)]
+struct Sarif<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun<'a>>,
+}
+
+#[derive(Serialize)]
+struct SarifRun<'a> {
+    tool: SarifTool,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id: String,
+    default_configuration: SarifLevel,
+}
+
+#[derive(Serialize)]
+struct SarifLevel {
+    level: Level,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    rule_id: &'a str,
+    level: Level,
+    message: SarifMessage<'a>,
+    locations: Vec<SarifLocation<'a>>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage<'a> {
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation<'a> {
+    physical_location: SarifPhysical<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysical<'a> {
+    artifact_location: SarifArtifact<'a>,
+}
+
+#[derive(Serialize)]
+struct SarifArtifact<'a> {
+    uri: &'a str,
+}
+
+pub fn export_sarif(report: &Report, rules: &Rules) -> io::Result<()> {
+    let sarif = Sarif {
+        schema: "https://json.schemastore.org/sarif-2.1.0.json",
+        version: "2.1.0",
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: "canopy",
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules: rules.rules.iter().map(|r| SarifRule { id: r.id.clone(), default_configuration: SarifLevel { level: r.level } }).collect(),
+                },
+            },
+            results: report
+                .violations
+                .iter()
+                .map(|v| SarifResult {
+                    rule_id: &v.rule,
+                    level: v.level,
+                    message: SarifMessage { text: &v.message },
+                    locations: vec![SarifLocation { physical_location: SarifPhysical { artifact_location: SarifArtifact { uri: &v.path } } }],
+                })
+                .collect(),
+        }],
+    };
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &sarif)?;
+    writeln!(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> TreeNode {
+        TreeNode { name: name.to_string(), size: Some(size), ..Default::default() }
+    }
+
+    fn dir(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode { name: name.to_string(), is_dir: true, children, ..Default::default() }
+    }
+
+    const RULES: &str = r#"
+        ignore = ["target"]
+
+        [[required]]
+        paths = ["crates/*"]
+        files = ["README.md"]
+
+        [[required]]
+        id = "license"
+        files = ["README.md", "LICENSE"]
+
+        [[forbidden]]
+        paths = ["*.orig"]
+        message = "leftover from a merge"
+
+        [[forbidden]]
+        paths = ["crates/b"]
+        level = "warning"
+
+        [[max_size]]
+        size = "5M"
+
+        [[max_depth]]
+        depth = 3
+    "#;
+
+    #[test]
+    fn reports_each_kind_of_rule() {
+        let tree = dir("repo", vec![
+            file("README.md", 10),
+            dir("crates", vec![dir("a", vec![file("README.md", 1)]), dir("b", vec![file("x.txt", 1)])]),
+            dir("deep", vec![dir("b", vec![dir("c", vec![dir("d", vec![file("x", 1)])])])]),
+            dir("src", vec![file("main.rs", 10), file("big.bin", 6 * 1024 * 1024), file("old.rs.orig", 1)]),
+            dir("target", vec![file("huge", 100 * 1024 * 1024), file("x.orig", 1)]),
+        ]);
+        let report = check(&tree, &load_rules(RULES).unwrap());
+        let found: Vec<(&str, Level, &str, &str)> =
+            report.violations.iter().map(|v| (v.rule.as_str(), v.level, v.path.as_str(), v.message.as_str())).collect();
+        assert_eq!(found, [
+            ("required-1", Level::Error, "crates/b", "missing README.md"),
+            ("license", Level::Error, ".", "missing LICENSE"),
+            ("forbidden-1", Level::Error, "src/old.rs.orig", "not allowed here (leftover from a merge)"),
+            // the folder once, not x.txt inside it again
+            ("forbidden-2", Level::Warning, "crates/b", "not allowed here"),
+            ("max_size-1", Level::Error, "src/big.bin", "6.0M is over the 5.0M limit"),
+            ("max_depth-1", Level::Error, "deep/b/c/d", "4 levels deep, the limit is 3"),
+        ]);
+        assert_eq!(report.count(Level::Warning), 1);
+        // everything but the ignored target folder
+        assert_eq!(report.checked, 15);
+    }
+
+    #[test]
+    fn whole_path_patterns() {
+        let rules = load_rules("[[required]]\nfiles = [\"src/lib.rs\"]\n[[forbidden]]\npaths = [\"src/*.tmp\"]").unwrap();
+        let tree = dir("repo", vec![dir("src", vec![file("lib.rs", 1), file("a.tmp", 1), dir("nested", vec![file("b.tmp", 1)])])]);
+        let report = check(&tree, &rules);
+        let paths: Vec<&str> = report.violations.iter().map(|v| v.path.as_str()).collect();
+        // `*` doesn't cross into src/nested
+        assert_eq!(paths, ["src/a.tmp"]);
+    }
+
+    #[test]
+    fn incomplete_rules_are_rejected() {
+        assert_eq!(load_rules("[[required]]\npaths = [\"x\"]").err().unwrap(), "required-1: required rules need `files`");
+        assert_eq!(load_rules("[[max_size]]\nsize = \"lots\"").err().unwrap(), "max_size-1: invalid size lots");
+        assert!(load_rules("[[forbidden]]\npath = [\"x\"]").is_err());
+    }
+}
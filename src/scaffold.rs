@@ -0,0 +1,414 @@
+use crate::icons::kind_for_dir;
+use crate::TreeNode;
+use colored::Colorize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// canopy scaffold: print_tree the other way round, a drawn tree becomes folders and files
+
+/// a spec in any shape canopy prints: a --json tree, the connector drawing (plain or in a markdown fence)
+/// or a markdown list. hand-indented text and `tree`'s output work too.
+/// the root canopy prints (the --json root, or the folder named like the title right under it) is the
+/// --into folder itself, its contents go in there. any other folder in the spec gets created
+pub fn parse_spec(text: &str) -> Result<TreeNode, String> {
+    if text.trim_start().starts_with('{') {
+        return serde_json::from_str(text).map_err(|e| e.to_string());
+    }
+    let mut lines: Vec<SpecLine> = text.lines().filter_map(parse_line).collect();
+    // canopy and `tree` print the path they were given above the drawing, that's a title, not an entry
+    let first = lines.iter().position(|l| l.connector).unwrap_or(0);
+    let title = lines.drain(..first).next_back().map(|l| l.name);
+    if lines.is_empty() {
+        return Err("no entries in the spec".to_string());
+    }
+    let mut top = build(&lines);
+    // canopy prints `proj` and then `└── [DIR] proj`, `tree` prints `proj` and then what's in it
+    if let (Some(title), [root]) = (title, top.as_slice()) {
+        if root.is_dir && draws_root(&title, &root.name) {
+            return Ok(top.remove(0));
+        }
+    }
+    Ok(TreeNode { is_dir: true, children: top, ..Default::default() })
+}
+
+/// whether `name` is what canopy draws for the path it was given as `title`.
+/// `.` and `..` have no file name of their own, canopy leaves the root's name empty for those
+fn draws_root(title: &str, name: &str) -> bool {
+    let last = title.trim_end_matches(['/', '\\']).rsplit(['/', '\\']).next().unwrap_or("");
+    name == last || (name.is_empty() && matches!(last, "." | ".." | ""))
+}
+
+struct SpecLine {
+    /// where the entry starts, children sit further right than their parent
+    column: usize,
+    name: String,
+    is_dir: bool,
+    connector: bool,
+}
+
+fn parse_line(line: &str) -> Option<SpecLine> {
+    let line = line.trim_end();
+    if line.trim().is_empty() || line.trim_start().starts_with("```") {
+        return None;
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let (mut i, mut column, mut connector, mut bullet) = (0, 0, false, false);
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        match chars[i] {
+            ' ' => column += 1,
+            '\t' => column += 4,
+            // `├── `, `└── ` and ascii `|-- `, `` `-- ``, `+-- `, `\-- `
+            '├' | '└' | '|' | '`' | '+' | '\\' if matches!(next, Some('─' | '-')) => {
+                i += 1;
+                while i < chars.len() && matches!(chars[i], '─' | '-') {
+                    i += 1;
+                }
+                connector = true;
+                break;
+            }
+            '│' | '|' => column += 1,
+            '-' | '*' | '+' if next == Some(' ') => {
+                i += 2;
+                bullet = true;
+                break;
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    // the tree drawing is one char per column, so where the connector ends is where the entry starts
+    if connector || bullet {
+        column = chars[..i].iter().map(|&c| if c == '\t' { 4 } else { 1 }).sum();
+    }
+    let text: String = chars[i..].iter().collect();
+    let (name, is_dir) = clean_name(text.trim(), bullet);
+    if name.is_empty() && !is_dir {
+        return None;
+    }
+    Some(SpecLine { column, name, is_dir, connector })
+}
+
+// what print_tree and the markdown list put around a name, peeled off again
+fn clean_name(text: &str, markdown: bool) -> (String, bool) {
+    let mut text = text;
+    // `  # note` in the tree, ` — note` in markdown lists
+    if let Some(i) = text.find("  # ") {
+        text = &text[..i];
+    }
+    if let (true, Some(i)) = (markdown, text.find(" — ")) {
+        text = &text[..i];
+    }
+    let mut text = text.trim().to_string();
+    if markdown {
+        // `[label](url)` when the list was made with --md-links, `**dir/**` for folders
+        if let (true, Some(i)) = (text.starts_with('[') && text.ends_with(')'), text.find("](")) {
+            text = text[1..i].to_string();
+        }
+        if let Some(inner) = text.strip_prefix("**").and_then(|t| t.strip_suffix("**")) {
+            text = inner.to_string();
+        }
+        text = unescape(&text);
+    }
+    // ` [12 loc]`, ` (3.1K)` and a dozen hash digits trail the name
+    if let Some(rest) = text.strip_suffix(" loc]").and_then(|t| t.rsplit_once(" [")) {
+        if rest.1.chars().all(|c| c.is_ascii_digit()) {
+            text = rest.0.to_string();
+        }
+    }
+    // folders the scan couldn't read are tagged with why
+    for tag in [" [permission denied]", " [vanished during scan]", " [read error]"] {
+        if let Some(rest) = text.strip_suffix(tag) {
+            text = rest.to_string();
+        }
+    }
+    if let Some((rest, hex)) = text.rsplit_once(' ') {
+        if hex.len() == 12 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            text = rest.to_string();
+        }
+    }
+    if let Some((rest, size)) = text.strip_suffix(')').and_then(|t| t.rsplit_once(" (")) {
+        if is_size(size) {
+            text = rest.to_string();
+        }
+    }
+    let (mut is_dir, name) = strip_icon(&text);
+    let mut name = name.to_string();
+    if name.len() > 1 && name.ends_with('/') {
+        name.pop();
+        is_dir = true;
+    }
+    (name, is_dir)
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// what format_size prints: `512B`, `3.1K`, `2.0M`, `1.5kB` with --si
+fn is_size(text: &str) -> bool {
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    digits.len() < text.len() && !digits.is_empty() && digits.parse::<f64>().is_ok()
+}
+
+/// the icon in front of the name and whether it was a folder's. the git marker sits before the icon
+fn strip_icon(text: &str) -> (bool, &str) {
+    // canopy draws the root of `.` as a folder icon with no name
+    if text == "[DIR]" {
+        return (true, "");
+    }
+    let text = match text.as_bytes() {
+        [b'!' | b'?' | b'S' | b'M' | b'U', b' ', ..] if has_icon(&text[2..]) => &text[2..],
+        _ => text,
+    };
+    if !has_icon(text) {
+        return (false, text);
+    }
+    let (icon, name) = text.split_once(' ').unwrap_or((text, ""));
+    let name = name.trim_start();
+    let is_dir = icon == "[DIR]" || icon == kind_for_dir(name).emoji || icon == kind_for_dir(name).nerd;
+    (is_dir, name)
+}
+
+// text icons are a short tag in brackets, nerd and emoji ones are a glyph without letters
+fn has_icon(text: &str) -> bool {
+    let Some((icon, _)) = text.split_once(' ') else { return false };
+    match icon.strip_prefix('[').and_then(|i| i.strip_suffix(']')) {
+        Some(tag) => !tag.is_empty() && tag.len() <= 12,
+        None => icon.chars().all(|c| !c.is_ascii() && !c.is_alphanumeric()),
+    }
+}
+
+// an entry's parent is the closest line above it that starts further left
+fn build(lines: &[SpecLine]) -> Vec<TreeNode> {
+    let mut top = Vec::new();
+    let mut stack: Vec<(usize, TreeNode)> = Vec::new();
+    for line in lines {
+        while stack.last().is_some_and(|(column, _)| *column >= line.column) {
+            close(&mut stack, &mut top);
+        }
+        stack.push((line.column, TreeNode { name: line.name.clone(), is_dir: line.is_dir, ..Default::default() }));
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut top);
+    }
+    top
+}
+
+fn close(stack: &mut Vec<(usize, TreeNode)>, top: &mut Vec<TreeNode>) {
+    let Some((_, node)) = stack.pop() else { return };
+    match stack.last_mut() {
+        Some((_, parent)) => {
+            parent.is_dir = true;
+            parent.children.push(node);
+        }
+        None => top.push(node),
+    }
+}
+
+/// what's on disk where an entry goes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Existing {
+    Nothing,
+    /// a folder already there for a folder, nothing to do
+    Same,
+    /// a file already there for a file, only --force writes over it
+    File,
+    /// a file where a folder goes or the other way round, never touched
+    Clash,
+}
+
+pub struct Step {
+    /// below the --into folder, `/` separated
+    pub rel: String,
+    pub is_dir: bool,
+    pub existing: Existing,
+}
+
+/// every folder and file the spec asks for, parents first
+pub fn plan(tree: &TreeNode, into: &Path) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    for child in &tree.children {
+        plan_node(child, "", into, &mut steps)?;
+    }
+    Ok(steps)
+}
+
+fn plan_node(node: &TreeNode, parent: &str, into: &Path, steps: &mut Vec<Step>) -> Result<(), String> {
+    // --collapse draws `src/main` as one entry, that's two folders
+    let parts: Vec<&str> = node.name.split(['/', '\\']).filter(|p| !p.is_empty() && *p != ".").collect();
+    if parts.is_empty() {
+        for child in &node.children {
+            plan_node(child, parent, into, steps)?;
+        }
+        return Ok(());
+    }
+    if parts.contains(&"..") {
+        return Err(format!("{} would reach outside the target folder", node.name));
+    }
+    let mut rel = parent.to_string();
+    for (i, part) in parts.iter().enumerate() {
+        rel = if rel.is_empty() { part.to_string() } else { format!("{}/{}", rel, part) };
+        let is_dir = node.is_dir || i < parts.len() - 1;
+        // two entries with the same name, or a folder made for a collapsed path already
+        if steps.iter().any(|s| s.rel == rel) {
+            continue;
+        }
+        let path = into.join(&rel);
+        let existing = match (path.symlink_metadata(), is_dir) {
+            (Err(_), _) => Existing::Nothing,
+            (Ok(md), true) if md.is_dir() => Existing::Same,
+            (Ok(md), false) if md.is_file() => Existing::File,
+            _ => Existing::Clash,
+        };
+        steps.push(Step { rel: rel.clone(), is_dir, existing });
+    }
+    for child in &node.children {
+        plan_node(child, &rel, into, steps)?;
+    }
+    Ok(())
+}
+
+/// --templates: a file gets the template at the same path below the folder, or else the one with its name.
+/// `{{name}}`, `{{path}}`, `{{project}}` and any --var in the template get filled in
+pub struct Templates {
+    pub dir: PathBuf,
+    pub vars: Vec<(String, String)>,
+}
+
+impl Templates {
+    fn content(&self, rel: &str, project: &str) -> io::Result<Vec<u8>> {
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        let template = [self.dir.join(rel), self.dir.join(name)].into_iter().find(|p| p.is_file());
+        let Some(template) = template else { return Ok(Vec::new()) };
+        let bytes = std::fs::read(template)?;
+        // binary templates (images, fonts) are copied as they are
+        let Ok(text) = String::from_utf8(bytes.clone()) else { return Ok(bytes) };
+        let builtin = [("name", name), ("path", rel), ("project", project)];
+        let mut text = text;
+        for (key, value) in builtin.iter().map(|(k, v)| (*k, *v)).chain(self.vars.iter().map(|(k, v)| (k.as_str(), v.as_str()))) {
+            text = text.replace(&format!("{{{{{}}}}}", key), value);
+        }
+        Ok(text.into_bytes())
+    }
+
+    fn has(&self, rel: &str) -> bool {
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        self.dir.join(rel).is_file() || self.dir.join(name).is_file()
+    }
+}
+
+/// one line per step, the same for --dry-run and the real thing
+pub fn print_plan(steps: &[Step], force: bool, templates: Option<&Templates>) {
+    for step in steps {
+        let shown = if step.is_dir { format!("{}/", step.rel) } else { step.rel.clone() };
+        let action = match (step.existing, step.is_dir, force) {
+            (Existing::Nothing, true, _) => "mkdir".green(),
+            (Existing::Nothing, false, _) => "create".green(),
+            (Existing::Same, _, _) => "exists".dimmed(),
+            (Existing::File, _, true) => "overwrite".yellow(),
+            (Existing::File, _, false) => "exists".red(),
+            (Existing::Clash, _, _) => "blocked".red(),
+        };
+        let from = match templates {
+            Some(t) if !step.is_dir && t.has(&step.rel) => " (from template)".dimmed().to_string(),
+            _ => String::new(),
+        };
+        println!("{:<9} {}{}", action, shown, from);
+    }
+}
+
+/// make the folders and write the files, the steps have been checked by then. returns (folders, files) made
+pub fn apply(steps: &[Step], into: &Path, templates: Option<&Templates>) -> io::Result<(usize, usize)> {
+    std::fs::create_dir_all(into)?;
+    let project = std::fs::canonicalize(into)?.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let (mut dirs, mut files) = (0, 0);
+    for step in steps.iter().filter(|s| s.existing != Existing::Same) {
+        let path = into.join(&step.rel);
+        if step.is_dir {
+            std::fs::create_dir_all(&path)?;
+            dirs += 1;
+        } else {
+            let content = match templates {
+                Some(t) => t.content(&step.rel, &project)?,
+                None => Vec::new(),
+            };
+            std::fs::write(&path, content)?;
+            files += 1;
+        }
+    }
+    Ok((dirs, files))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(node: &TreeNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn canopy_root_is_the_target_folder() {
+        let tree = parse_spec("proj\n└── [DIR] proj\n    ├── [rs] main.rs (2B)\n    └── [DIR] src\n        └── [rs] lib.rs\n").unwrap();
+        assert_eq!(names(&tree), ["main.rs", "src"]);
+        assert!(tree.children[1].is_dir);
+        assert_eq!(names(&tree.children[1]), ["lib.rs"]);
+
+        let tree = parse_spec("/home/me/proj/\n└── [DIR] proj\n    └── [rs] main.rs\n").unwrap();
+        assert_eq!(names(&tree), ["main.rs"]);
+    }
+
+    #[test]
+    fn canopy_root_of_dot_has_no_name() {
+        let tree = parse_spec(".\n└── [DIR] \n    └── [rs] a.rs\n").unwrap();
+        assert_eq!(names(&tree), ["a.rs"]);
+    }
+
+    #[test]
+    fn tree_output_keeps_its_top_folder() {
+        let tree = parse_spec(".\n└── src\n    └── main.rs\n").unwrap();
+        assert_eq!(names(&tree), ["src"]);
+        assert!(tree.children[0].is_dir);
+        assert_eq!(names(&tree.children[0]), ["main.rs"]);
+
+        let tree = parse_spec("proj\n├── Cargo.toml\n└── src\n    └── main.rs\n").unwrap();
+        assert_eq!(names(&tree), ["Cargo.toml", "src"]);
+    }
+
+    #[test]
+    fn markdown_list_keeps_its_top_folder() {
+        let tree = parse_spec("- src/\n  - main.rs\n").unwrap();
+        assert_eq!(names(&tree), ["src"]);
+        assert_eq!(names(&tree.children[0]), ["main.rs"]);
+
+        let tree = parse_spec("- **src/**\n  - [main.rs](src/main.rs) — entry point\n- README.md\n").unwrap();
+        assert_eq!(names(&tree), ["src", "README.md"]);
+        assert_eq!(names(&tree.children[0]), ["main.rs"]);
+    }
+
+    #[test]
+    fn markdown_fence_title_is_dropped() {
+        let tree = parse_spec("```text\nproj/\n├── a.rs\n└── b/\n    └── c.rs\n```\n").unwrap();
+        assert_eq!(names(&tree), ["a.rs", "b"]);
+    }
+
+    #[test]
+    fn json_root_is_the_target_folder() {
+        let tree = parse_spec(r#"{"name": "proj", "is_dir": true, "size": null, "children": [{"name": "a.rs", "is_dir": false, "size": 2, "children": []}]}"#).unwrap();
+        assert_eq!(names(&tree), ["a.rs"]);
+    }
+
+    #[test]
+    fn empty_spec_is_an_error() {
+        assert!(parse_spec("\n\n").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::icons::{extension_of, kind_for_file};
+use crate::stream::Sink;
+use crate::{SizeStyle, TreeNode};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+// --stats: what the scan is made of, by extension, kind, entry type and depth
+
+#[derive(Serialize, Default, Clone, Copy)]
+pub struct Bucket {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+impl Bucket {
+    fn add(&mut self, bytes: u64) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+}
+
+#[derive(Serialize)]
+pub struct Deepest {
+    pub path: String,
+    pub depth: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct Stats {
+    pub extensions: BTreeMap<String, Bucket>,
+    pub categories: BTreeMap<&'static str, Bucket>,
+    pub types: BTreeMap<&'static str, Bucket>,
+    pub depths: BTreeMap<usize, Bucket>,
+    pub deepest: Option<Deepest>,
+    pub longest_name: Option<String>,
+    pub empty_files: Vec<String>,
+    pub empty_dirs: Vec<String>,
+    /// --depth the tree was scanned with, folders below it were never opened so they aren't empty
+    #[serde(skip)]
+    pub max_depth: Option<usize>,
+}
+
+/// fed by the walker or a replayed tree, the root itself isn't counted
+impl Sink for Stats {
+    fn entry(&mut self, node: &TreeNode, path: &str, depth: usize, has_children: bool) -> io::Result<()> {
+        if depth == 0 {
+            return Ok(());
+        }
+        let size = node.size.unwrap_or(0);
+        let kind = match (node.is_dir, node.special) {
+            (true, _) => "dir",
+            (false, Some(special)) => special.name(),
+            (false, None) => "file",
+        };
+        self.types.entry(kind).or_default().add(size);
+        self.depths.entry(depth).or_default().add(size);
+        if self.deepest.as_ref().is_none_or(|d| depth > d.depth) {
+            self.deepest = Some(Deepest { path: path.to_string(), depth });
+        }
+        let name_len = node.name.chars().count();
+        if self.longest_name.as_ref().is_none_or(|p| name_len > file_name(p).chars().count()) {
+            self.longest_name = Some(path.to_string());
+        }
+
+        if node.is_dir {
+            let walked = self.max_depth.is_none_or(|d| depth <= d) && node.error.is_none();
+            if walked && !has_children {
+                self.empty_dirs.push(path.to_string());
+            }
+            return Ok(());
+        }
+        if kind != "file" {
+            return Ok(());
+        }
+        let ext = extension_of(&node.name).unwrap_or_else(|| "(none)".to_string());
+        self.extensions.entry(ext).or_default().add(size);
+        // same registry the icons come from, so a Dockerfile counts as build even without an extension
+        // and with --detect a renamed photo counts as an image
+        let category = kind_for_file(&node.name, node.detected.as_deref()).map(|k| k.category.name()).unwrap_or("other");
+        self.categories.entry(category).or_default().add(size);
+        if size == 0 {
+            self.empty_files.push(path.to_string());
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, _depth: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+// biggest first, that's what anyone reading this is looking for
+fn by_bytes<K: Clone>(map: &BTreeMap<K, Bucket>) -> Vec<(K, Bucket)> {
+    let mut rows: Vec<(K, Bucket)> = map.iter().map(|(k, b)| (k.clone(), *b)).collect();
+    rows.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(b.1.count.cmp(&a.1.count)));
+    rows
+}
+
+impl Stats {
+    pub fn print(&self, sizes: &SizeStyle) {
+        let section = |title: &str, rows: Vec<(String, Bucket)>| {
+            if rows.is_empty() {
+                return;
+            }
+            println!();
+            println!("{}", title.bold());
+            for (key, b) in rows {
+                let size = format!("{:>10}", sizes.format(b.bytes));
+                println!("  {:<16} {:>8}  {}", key, b.count, size.color(sizes.color(b.bytes)));
+            }
+        };
+        let owned = |rows: Vec<(&'static str, Bucket)>| rows.into_iter().map(|(k, b)| (k.to_string(), b)).collect();
+        section("By extension", by_bytes(&self.extensions));
+        section("By kind", owned(by_bytes(&self.categories)));
+        section("By type", owned(by_bytes(&self.types)));
+        section("By depth", self.depths.iter().map(|(d, b)| (d.to_string(), *b)).collect());
+
+        println!();
+        if let Some(deepest) = &self.deepest {
+            println!("{} {} (depth {})", "Deepest path: ".bold(), deepest.path, deepest.depth);
+        }
+        if let Some(path) = &self.longest_name {
+            println!("{} {} ({} chars)", "Longest name: ".bold(), path, file_name(path).chars().count());
+        }
+        for (title, paths) in [("Empty files:  ", &self.empty_files), ("Empty dirs:   ", &self.empty_dirs)] {
+            println!("{} {}", title.bold(), paths.len());
+            // the full list is in the json/csv output, the summary only needs a taste
+            for path in paths.iter().take(10) {
+                println!("    {}", path.dimmed());
+            }
+            if paths.len() > 10 {
+                println!("    {}", format!("...and {} more", paths.len() - 10).dimmed());
+            }
+        }
+    }
+
+    pub fn export_json(&self) -> io::Result<()> {
+        println!("{}", serde_json::to_string_pretty(self)?);
+        Ok(())
+    }
+
+    /// one row per bucket or finding: section,key,count,bytes
+    pub fn export_csv(&self) -> io::Result<()> {
+        let mut wtr = csv::Writer::from_writer(io::stdout().lock());
+        wtr.write_record(["section", "key", "count", "bytes"])?;
+        let mut bucket = |section: &str, key: &str, b: &Bucket| wtr.write_record([section, key, &b.count.to_string(), &b.bytes.to_string()]);
+        for (k, b) in by_bytes(&self.extensions) {
+            bucket("extension", &k, &b)?;
+        }
+        for (k, b) in by_bytes(&self.categories) {
+            bucket("kind", k, &b)?;
+        }
+        for (k, b) in by_bytes(&self.types) {
+            bucket("type", k, &b)?;
+        }
+        for (d, b) in &self.depths {
+            bucket("depth", &d.to_string(), b)?;
+        }
+        if let Some(deepest) = &self.deepest {
+            wtr.write_record(["deepest", &deepest.path, &deepest.depth.to_string(), ""])?;
+        }
+        if let Some(path) = &self.longest_name {
+            wtr.write_record(["longest_name", path, &file_name(path).chars().count().to_string(), ""])?;
+        }
+        for path in &self.empty_files {
+            wtr.write_record(["empty_file", path, "", "0"])?;
+        }
+        for path in &self.empty_dirs {
+            wtr.write_record(["empty_dir", path, "", ""])?;
+        }
+        wtr.flush()?;
+        io::stdout().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::replay;
+
+    fn dir(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode { name: name.to_string(), is_dir: true, children, ..Default::default() }
+    }
+
+    fn file(name: &str, size: u64) -> TreeNode {
+        TreeNode { name: name.to_string(), size: Some(size), ..Default::default() }
+    }
+
+    #[test]
+    fn counts_by_extension_and_depth() {
+        let tree = dir("", vec![file("a.rs", 10), dir("src", vec![file("b.rs", 5), file("c.md", 0)])]);
+        let mut stats = Stats::default();
+        replay(&tree, "", 0, &mut stats).unwrap();
+        assert_eq!(stats.extensions["rs"].count, 2);
+        assert_eq!(stats.extensions["rs"].bytes, 15);
+        assert_eq!(stats.depths[&1].count, 2);
+        assert_eq!(stats.depths[&2].count, 2);
+        assert_eq!(stats.empty_files, ["src/c.md"]);
+        assert_eq!(stats.deepest.unwrap().path, "src/b.rs");
+    }
+
+    #[test]
+    fn only_walked_folders_are_empty() {
+        // d/a/b/c scanned with --depth 1: a was listed, a/b was only seen
+        let tree = dir("", vec![dir("a", vec![dir("b", Vec::new())]), dir("empty", Vec::new())]);
+        let mut stats = Stats { max_depth: Some(1), ..Default::default() };
+        replay(&tree, "", 0, &mut stats).unwrap();
+        assert_eq!(stats.empty_dirs, ["empty"]);
+
+        let locked = TreeNode { error: Some(crate::ScanError::from_io(&io::Error::from(io::ErrorKind::PermissionDenied))), ..dir("locked", Vec::new()) };
+        let mut stats = Stats::default();
+        replay(&dir("", vec![locked, dir("empty", Vec::new())]), "", 0, &mut stats).unwrap();
+        assert_eq!(stats.empty_dirs, ["empty"]);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::formats::FlatEntry;
+use crate::{dir_node, get_entries, leaf_node, ScanError, TreeNode};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// streaming exports: walk the disk and write each entry as soon as we see it instead of
+// building the whole TreeNode first. memory stays at one folder listing per level and
+// the first rows show up right away, which matters on trees with millions of files
+
+pub struct WalkOptions<'a> {
+    pub max_depth: Option<usize>,
+    pub show_hidden: bool,
+    pub filter: Option<&'a str>,
+    pub with_meta: bool,
+}
+
+pub trait Sink {
+    /// called for every entry in tree order, `node.children` may or may not be filled in.
+    /// `has_children` folders get a matching `leave` once their children are done
+    fn entry(&mut self, node: &TreeNode, path: &str, depth: usize, has_children: bool) -> io::Result<()>;
+    fn leave(&mut self, depth: usize) -> io::Result<()>;
+}
+
+/// the entries that couldn't be read come back for the summary, the sink saw them already with their `error`.
+/// only the starting folder being unreadable is an error
+pub fn walk<S: Sink>(path: &Path, rel: &str, opts: &WalkOptions, sink: &mut S) -> io::Result<Vec<(PathBuf, ScanError)>> {
+    let mut errors = Vec::new();
+    walk_at(path, rel, 0, opts.max_depth, opts, sink, &mut errors)?;
+    Ok(errors)
+}
+
+type Errors = Vec<(PathBuf, ScanError)>;
+
+fn walk_at<S: Sink>(path: &Path, rel: &str, depth: usize, max_depth: Option<usize>, opts: &WalkOptions, sink: &mut S, errors: &mut Errors) -> io::Result<()> {
+    let mut node = dir_node(path, opts.with_meta);
+    let entries = match get_entries(path, opts.show_hidden, opts.filter) {
+        Ok(entries) => entries,
+        Err(e) if depth > 0 => {
+            let error = ScanError::from_io(&e);
+            errors.push((path.to_path_buf(), error.clone()));
+            node.error = Some(error);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+    let has_children = !entries.is_empty();
+    sink.entry(&node, rel, depth, has_children)?;
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let child_rel = if rel.is_empty() {
+            name
+        } else {
+            format!("{}/{}", rel.trim_end_matches(['/', '\\']), name)
+        };
+        let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
+        if is_dir && max_depth.is_none_or(|d| d > 0) {
+            walk_at(&entry.path(), &child_rel, depth + 1, max_depth.map(|d| d - 1), opts, sink, errors)?;
+        } else {
+            let leaf = leaf_node(&entry, opts.with_meta);
+            if let Some(error) = &leaf.error {
+                errors.push((entry.path(), error.clone()));
+            }
+            sink.entry(&leaf, &child_rel, depth + 1, false)?;
+        }
+    }
+    if has_children {
+        sink.leave(depth)?;
+    }
+    Ok(())
+}
+
+/// feed an already built tree through a sink, for when --collapse or --annotations needed the whole thing
+pub fn replay<S: Sink>(node: &TreeNode, rel: &str, depth: usize, sink: &mut S) -> io::Result<()> {
+    let has_children = !node.children.is_empty();
+    sink.entry(node, rel, depth, has_children)?;
+    for child in &node.children {
+        let child_rel = if rel.is_empty() {
+            child.name.clone()
+        } else {
+            format!("{}/{}", rel.trim_end_matches(['/', '\\']), child.name)
+        };
+        replay(child, &child_rel, depth + 1, sink)?;
+    }
+    if has_children {
+        sink.leave(depth)?;
+    }
+    Ok(())
+}
+
+/// writes exactly what `serde_json::to_string_pretty` would for the whole tree, one node at a time
+pub struct JsonSink<W: Write> {
+    out: W,
+    // per open folder: has it written a child yet
+    open: Vec<bool>,
+}
+
+impl<W: Write> JsonSink<W> {
+    pub fn new(out: W) -> JsonSink<W> {
+        JsonSink { out, open: Vec::new() }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Sink for JsonSink<W> {
+    fn entry(&mut self, node: &TreeNode, _path: &str, depth: usize, has_children: bool) -> io::Result<()> {
+        if let Some(first_done) = self.open.last_mut() {
+            if *first_done {
+                self.out.write_all(b",")?;
+            }
+            *first_done = true;
+            self.out.write_all(b"\n")?;
+        }
+        // replayed folders come with their children, they get written one by one after this
+        let pretty = match node.children.is_empty() {
+            true => serde_json::to_string_pretty(node)?,
+            false => serde_json::to_string_pretty(&without_children(node))?,
+        };
+        let indent = " ".repeat(depth * 4);
+        let pretty = if has_children {
+            // serde ends a childless node with `"children": []\n}`, chop that and leave the array open
+            let cut = pretty.rfind("[]").unwrap_or(pretty.len());
+            format!("{}[", &pretty[..cut])
+        } else {
+            pretty
+        };
+        for (i, line) in pretty.lines().enumerate() {
+            if i > 0 {
+                self.out.write_all(b"\n")?;
+            }
+            write!(self.out, "{}{}", indent, line)?;
+        }
+        if has_children {
+            self.open.push(false);
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, depth: usize) -> io::Result<()> {
+        self.open.pop();
+        let indent = " ".repeat(depth * 4);
+        write!(self.out, "\n{}  ]\n{}}}", indent, indent)?;
+        // one flush per folder keeps output flowing without a syscall per line
+        self.out.flush()
+    }
+}
+
+// spelled out instead of `..node.clone()` so a big folder isn't copied whole just to drop its children
+fn without_children(node: &TreeNode) -> TreeNode {
+    TreeNode {
+        name: node.name.clone(),
+        is_dir: node.is_dir,
+        size: node.size,
+        mode: node.mode,
+        owner: node.owner.clone(),
+        group: node.group.clone(),
+        mtime: node.mtime,
+        hash: node.hash.clone(),
+        note: node.note.clone(),
+        loc: node.loc,
+        detected: node.detected.clone(),
+        git: node.git,
+        error: node.error.clone(),
+        special: node.special,
+        children: Vec::new(),
+    }
+}
+
+pub struct NdjsonSink<W: Write> {
+    pub out: W,
+}
+
+impl<W: Write> Sink for NdjsonSink<W> {
+    fn entry(&mut self, node: &TreeNode, path: &str, _depth: usize, _has_children: bool) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, &FlatEntry::new(node, path))?;
+        self.out.write_all(b"\n")
+    }
+
+    fn leave(&mut self, _depth: usize) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loc::Loc;
+    use crate::ScanErrorKind;
+
+    fn file(name: &str, size: u64) -> TreeNode {
+        TreeNode { name: name.to_string(), size: Some(size), ..Default::default() }
+    }
+
+    fn dir(name: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode { name: name.to_string(), is_dir: true, children, ..Default::default() }
+    }
+
+    fn streamed(tree: &TreeNode) -> String {
+        let mut out = Vec::new();
+        let mut sink = JsonSink::new(&mut out);
+        replay(tree, "", 0, &mut sink).unwrap();
+        sink.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn assert_same(tree: &TreeNode) {
+        assert_eq!(streamed(tree), serde_json::to_string_pretty(tree).unwrap() + "\n");
+    }
+
+    #[test]
+    fn json_sink_matches_serde() {
+        assert_same(&dir("empty", vec![]));
+        assert_same(&file("lonely.txt", 3));
+        assert_same(&dir("root", vec![
+            file("a.txt", 1),
+            dir("empty", vec![]),
+            dir("src", vec![dir("deep", vec![file("x.rs", 10), file("y.rs", 20)]), file("main.rs", 5)]),
+            file("z", 0),
+        ]));
+    }
+
+    #[test]
+    fn json_sink_matches_serde_with_extra_fields() {
+        let mut locked = dir("locked", vec![]);
+        locked.error = Some(ScanError { kind: ScanErrorKind::PermissionDenied, message: "Permission denied".to_string() });
+        let mut code = file("lib.rs", 42);
+        code.mode = Some(0o100644);
+        code.mtime = Some(1_700_000_000);
+        code.loc = Some(Loc { lines: 3, code: 2, comments: 1, blank: 0 });
+        let mut root = dir("root", vec![locked, dir("src", vec![code]), file("quote\"d \\ name", 1)]);
+        root.mode = Some(0o040755);
+        assert_same(&root);
+    }
+}
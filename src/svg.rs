@@ -0,0 +1,367 @@
+use crate::icons::extension_of;
+use crate::{escape_xml, join_path, total_size, SizeStyle, TreeNode};
+use std::f64::consts::PI;
+use std::fmt::Write as _;
+
+// --export svg-treemap / svg-sunburst, disk usage pictures for storage reviews
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SvgColor {
+    Extension,
+    Depth,
+}
+
+pub struct SvgOptions<'a> {
+    pub color: SvgColor,
+    /// anything smaller than this many square pixels gets lumped into one "small files" block
+    pub min_area: f64,
+    pub sizes: &'a SizeStyle,
+}
+
+const TREEMAP_W: f64 = 1280.0;
+const TREEMAP_H: f64 = 800.0;
+const SUNBURST: f64 = 900.0;
+const HEADER: f64 = 16.0;
+
+// what actually gets drawn: a real entry, or a pile of tiny ones
+enum Item<'a> {
+    Node(&'a TreeNode, u64),
+    Small(usize, u64),
+}
+
+impl Item<'_> {
+    fn size(&self) -> u64 {
+        match self {
+            Item::Node(_, s) | Item::Small(_, s) => *s,
+        }
+    }
+}
+
+/// children with their sizes, biggest first, with everything under `min_share` of the parent grouped
+fn items(node: &TreeNode, min_share: f64) -> Vec<Item<'_>> {
+    let mut sized: Vec<(&TreeNode, u64)> = node.children.iter().map(|c| (c, total_size(c))).filter(|(_, s)| *s > 0).collect();
+    sized.sort_by_key(|(_, s)| std::cmp::Reverse(*s));
+    let total: u64 = sized.iter().map(|(_, s)| s).sum();
+    let (mut out, small): (Vec<_>, Vec<_>) = sized.into_iter().partition(|(_, size)| total == 0 || (*size as f64 / total as f64) >= min_share);
+    let mut out: Vec<Item> = out.drain(..).map(|(child, size)| Item::Node(child, size)).collect();
+    match small.as_slice() {
+        [] => {}
+        // one tiny thing on its own isn't worth a "1 small files" label, it's drawn as itself
+        [(child, size)] => out.push(Item::Node(child, *size)),
+        _ => out.push(Item::Small(small.len(), small.iter().map(|(_, s)| s).sum())),
+    }
+    out
+}
+
+fn hue_for(text: &str) -> u32 {
+    // fnv-1a, just needs to be stable so .rs is always the same color
+    let mut h: u32 = 0x811c9dc5;
+    for b in text.bytes() {
+        h ^= b as u32;
+        h = h.wrapping_mul(0x01000193);
+    }
+    h % 360
+}
+
+fn file_fill(name: &str, depth: usize, color: SvgColor) -> String {
+    match color {
+        SvgColor::Depth => format!("hsl({},55%,62%)", (depth * 47) % 360),
+        SvgColor::Extension => match extension_of(name) {
+            Some(ext) => format!("hsl({},58%,60%)", hue_for(&ext)),
+            None => "hsl(0,0%,70%)".to_string(),
+        },
+    }
+}
+
+fn dir_fill(depth: usize, color: SvgColor) -> String {
+    match color {
+        SvgColor::Depth => format!("hsl({},40%,{}%)", (depth * 47) % 360, 85 - (depth.min(6) * 3)),
+        SvgColor::Extension => format!("hsl(215,25%,{}%)", 90 - (depth.min(8) * 5)),
+    }
+}
+
+fn tooltip(path: &str, size: u64, sizes: &SizeStyle) -> String {
+    format!("<title>{} — {} ({} bytes)</title>", escape_xml(path), sizes.format(size), size)
+}
+
+// rough fit check for labels, 11px sans is about 6.5px a character
+fn fits(text: &str, w: f64) -> bool {
+    text.chars().count() as f64 * 6.5 + 6.0 <= w
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+/// squarified treemap (bruls, huizing, van wijk), keeps the boxes close to square
+fn squarify(areas: &[f64], rect: Rect) -> Vec<Rect> {
+    let mut out = Vec::with_capacity(areas.len());
+    let mut rest = rect;
+    let mut row: Vec<f64> = Vec::new();
+    let worst = |row: &[f64], side: f64| -> f64 {
+        let sum: f64 = row.iter().sum();
+        let max = row.iter().cloned().fold(f64::MIN, f64::max);
+        let min = row.iter().cloned().fold(f64::MAX, f64::min);
+        let s2 = sum * sum;
+        let side2 = side * side;
+        (side2 * max / s2).max(s2 / (side2 * min))
+    };
+    let mut i = 0;
+    while i < areas.len() {
+        let side = rest.w.min(rest.h);
+        let mut candidate = row.clone();
+        candidate.push(areas[i]);
+        if row.is_empty() || worst(&candidate, side) <= worst(&row, side) {
+            row = candidate;
+            i += 1;
+        } else {
+            rest = lay_row(&row, rest, &mut out);
+            row.clear();
+        }
+    }
+    if !row.is_empty() {
+        lay_row(&row, rest, &mut out);
+    }
+    out
+}
+
+fn lay_row(row: &[f64], rect: Rect, out: &mut Vec<Rect>) -> Rect {
+    let sum: f64 = row.iter().sum();
+    if rect.w >= rect.h {
+        // column down the left side
+        let col_w = if rect.h > 0.0 { sum / rect.h } else { 0.0 };
+        let mut y = rect.y;
+        for a in row {
+            let h = if col_w > 0.0 { a / col_w } else { 0.0 };
+            out.push(Rect { x: rect.x, y, w: col_w, h });
+            y += h;
+        }
+        Rect { x: rect.x + col_w, y: rect.y, w: (rect.w - col_w).max(0.0), h: rect.h }
+    } else {
+        // row across the top
+        let row_h = if rect.w > 0.0 { sum / rect.w } else { 0.0 };
+        let mut x = rect.x;
+        for a in row {
+            let w = if row_h > 0.0 { a / row_h } else { 0.0 };
+            out.push(Rect { x, y: rect.y, w, h: row_h });
+            x += w;
+        }
+        Rect { x: rect.x, y: rect.y + row_h, w: rect.w, h: (rect.h - row_h).max(0.0) }
+    }
+}
+
+pub fn export_treemap(tree: &TreeNode, root_name: &str, opts: &SvgOptions) -> std::io::Result<()> {
+    let mut out = String::new();
+    let total = total_size(tree);
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="Helvetica, Arial, sans-serif" font-size="11">"#,
+        w = TREEMAP_W,
+        h = TREEMAP_H + 24.0
+    );
+    let _ = writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(
+        out,
+        r#"<text x="6" y="16" font-size="13" font-weight="bold">{} — {}</text>"#,
+        escape_xml(root_name),
+        opts.sizes.format(total)
+    );
+    let rect = Rect { x: 0.0, y: 24.0, w: TREEMAP_W, h: TREEMAP_H };
+    treemap_dir(&mut out, tree, root_name, rect, 0, opts);
+    out.push_str("</svg>\n");
+    print!("{}", out);
+    Ok(())
+}
+
+fn treemap_dir(out: &mut String, node: &TreeNode, path: &str, rect: Rect, depth: usize, opts: &SvgOptions) {
+    let size = total_size(node);
+    let _ = writeln!(
+        out,
+        r#"<g><rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}" stroke="white" stroke-width="1">{}</rect>"#,
+        rect.x, rect.y, rect.w, rect.h, dir_fill(depth, opts.color), tooltip(path, size, opts.sizes)
+    );
+    let mut inner = Rect { x: rect.x + 2.0, y: rect.y + 2.0, w: (rect.w - 4.0).max(0.0), h: (rect.h - 4.0).max(0.0) };
+    if depth > 0 && rect.h > HEADER * 2.0 && rect.w > 40.0 {
+        // folder name in a little header strip, if there's room for one
+        let label = format!("{}/", node.name);
+        if fits(&label, rect.w) {
+            let _ = writeln!(out, r##"<text x="{:.1}" y="{:.1}" fill="#333">{}</text>"##, rect.x + 4.0, rect.y + 12.0, escape_xml(&label));
+        }
+        inner.y += HEADER - 2.0;
+        inner.h = (inner.h - HEADER + 2.0).max(0.0);
+    }
+    out.push_str("</g>\n");
+    let area = inner.w * inner.h;
+    if area <= 0.0 {
+        return;
+    }
+    let min_share = opts.min_area / area;
+    let items = items(node, min_share);
+    let sum: u64 = items.iter().map(Item::size).sum();
+    if sum == 0 {
+        return;
+    }
+    let areas: Vec<f64> = items.iter().map(|i| i.size() as f64 / sum as f64 * area).collect();
+    let rects = squarify(&areas, inner);
+    for (item, r) in items.iter().zip(rects) {
+        match item {
+            Item::Node(child, _) if child.is_dir => {
+                treemap_dir(out, child, &join_path(path, &child.name), r, depth + 1, opts);
+            }
+            Item::Node(child, child_size) => {
+                let child_path = join_path(path, &child.name);
+                let fill = file_fill(&child.name, depth + 1, opts.color);
+                treemap_leaf(out, &child.name, &child_path, *child_size, r, &fill, opts);
+            }
+            Item::Small(count, small_size) => {
+                let label = format!("{} small files", count);
+                let small_path = format!("{} ({})", join_path(path, "…"), label);
+                treemap_leaf(out, &label, &small_path, *small_size, r, "hsl(0,0%,82%)", opts);
+            }
+        }
+    }
+}
+
+fn treemap_leaf(out: &mut String, label: &str, path: &str, size: u64, r: Rect, fill: &str, opts: &SvgOptions) {
+    let _ = write!(
+        out,
+        r#"<g><rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}" stroke="white" stroke-width="0.5">{}</rect>"#,
+        r.x, r.y, r.w, r.h, fill, tooltip(path, size, opts.sizes)
+    );
+    if r.h >= 14.0 && fits(label, r.w) {
+        let _ = write!(out, r##"<text x="{:.1}" y="{:.1}" fill="#1e1e1e" pointer-events="none">{}</text>"##, r.x + 3.0, r.y + 12.0, escape_xml(label));
+    }
+    out.push_str("</g>\n");
+}
+
+/// how many rings the sunburst needs below the center
+fn levels(node: &TreeNode) -> usize {
+    node.children.iter().map(|c| 1 + if c.is_dir { levels(c) } else { 0 }).max().unwrap_or(0)
+}
+
+pub fn export_sunburst(tree: &TreeNode, root_name: &str, opts: &SvgOptions) -> std::io::Result<()> {
+    let mut out = String::new();
+    let total = total_size(tree);
+    let c = SUNBURST / 2.0;
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{s}" height="{s}" viewBox="0 0 {s} {s}" font-family="Helvetica, Arial, sans-serif" font-size="12">"#,
+        s = SUNBURST
+    );
+    let _ = writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    // more than ~10 rings is unreadable anyway, deeper stuff just isn't drawn
+    let levels = levels(tree).clamp(1, 10);
+    let center = 70.0;
+    let ring = (c - 10.0 - center) / levels as f64;
+    let _ = writeln!(
+        out,
+        r#"<g><circle cx="{c}" cy="{c}" r="{center}" fill="{}">{}</circle><text x="{c}" y="{:.1}" text-anchor="middle" font-weight="bold">{}</text><text x="{c}" y="{:.1}" text-anchor="middle">{}</text></g>"#,
+        dir_fill(0, opts.color),
+        tooltip(root_name, total, opts.sizes),
+        c - 2.0,
+        escape_xml(root_name),
+        c + 14.0,
+        opts.sizes.format(total)
+    );
+    let geom = Ring { c, center, ring, levels };
+    sunburst_level(&mut out, tree, root_name, 0, 0.0, 2.0 * PI, &geom, opts);
+    out.push_str("</svg>\n");
+    print!("{}", out);
+    Ok(())
+}
+
+struct Ring {
+    c: f64,
+    center: f64,
+    ring: f64,
+    levels: usize,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sunburst_level(out: &mut String, node: &TreeNode, path: &str, depth: usize, a0: f64, a1: f64, geom: &Ring, opts: &SvgOptions) {
+    if depth >= geom.levels {
+        return;
+    }
+    let r_in = geom.center + depth as f64 * geom.ring;
+    let r_out = r_in + geom.ring;
+    let span_area = (a1 - a0) / 2.0 * (r_out * r_out - r_in * r_in);
+    if span_area <= 0.0 {
+        return;
+    }
+    let items = items(node, opts.min_area / span_area);
+    let sum: u64 = items.iter().map(Item::size).sum();
+    if sum == 0 {
+        return;
+    }
+    let mut start = a0;
+    for item in &items {
+        let span = (a1 - a0) * item.size() as f64 / sum as f64;
+        let end = start + span;
+        let d = arc_path(geom.c, r_in, r_out, start, end);
+        match item {
+            Item::Node(child, size) => {
+                let child_path = join_path(path, &child.name);
+                let fill = if child.is_dir { dir_fill(depth + 1, opts.color) } else { file_fill(&child.name, depth + 1, opts.color) };
+                let _ = writeln!(out, r#"<path d="{}" fill="{}" stroke="white" stroke-width="0.7">{}</path>"#, d, fill, tooltip(&child_path, *size, opts.sizes));
+                if child.is_dir {
+                    sunburst_level(out, child, &child_path, depth + 1, start, end, geom, opts);
+                }
+            }
+            Item::Small(count, size) => {
+                let label = format!("{} ({} small files)", join_path(path, "…"), count);
+                let _ = writeln!(out, r#"<path d="{}" fill="hsl(0,0%,82%)" stroke="white" stroke-width="0.7">{}</path>"#, d, tooltip(&label, *size, opts.sizes));
+            }
+        }
+        start = end;
+    }
+}
+
+fn arc_path(c: f64, r_in: f64, r_out: f64, a0: f64, a1: f64) -> String {
+    // a full circle can't be one arc command, shave a hair off
+    let a1 = if a1 - a0 >= 2.0 * PI { a0 + 2.0 * PI - 1e-4 } else { a1 };
+    let large = if a1 - a0 > PI { 1 } else { 0 };
+    let pt = |r: f64, a: f64| (c + r * (a - PI / 2.0).cos(), c + r * (a - PI / 2.0).sin());
+    let (x0, y0) = pt(r_out, a0);
+    let (x1, y1) = pt(r_out, a1);
+    let (x2, y2) = pt(r_in, a1);
+    let (x3, y3) = pt(r_in, a0);
+    format!(
+        "M{:.2},{:.2} A{:.2},{:.2} 0 {} 1 {:.2},{:.2} L{:.2},{:.2} A{:.2},{:.2} 0 {} 0 {:.2},{:.2} Z",
+        x0, y0, r_out, r_out, large, x1, y1, x2, y2, r_in, r_in, large, x3, y3
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> TreeNode {
+        TreeNode { name: name.to_string(), size: Some(size), ..Default::default() }
+    }
+
+    fn shares(node: &TreeNode) -> Vec<(String, u64)> {
+        items(node, 0.05)
+            .iter()
+            .map(|i| match i {
+                Item::Node(n, s) => (n.name.clone(), *s),
+                Item::Small(count, s) => (format!("{} small", count), *s),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_items_are_grouped_and_nothing_goes_missing() {
+        let node = TreeNode { is_dir: true, children: vec![file("big", 900), file("a", 10), file("b", 20), file("empty", 0)], ..Default::default() };
+        assert_eq!(shares(&node), [("big".to_string(), 900), ("2 small".to_string(), 30)]);
+    }
+
+    #[test]
+    fn a_single_small_item_is_drawn_as_itself() {
+        let node = TreeNode { is_dir: true, children: vec![file("big", 990), file("tiny", 10)], ..Default::default() };
+        assert_eq!(shares(&node), [("big".to_string(), 990), ("tiny".to_string(), 10)]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::stream::Sink;
+use crate::TreeNode;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// --export csv / tsv, one row per entry for spreadsheets and database imports
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CsvColumn {
+    Name,
+    Path,
+    AbsPath,
+    Parent,
+    Depth,
+    Ext,
+    Type,
+    Size,
+    Mtime,
+    Mode,
+    Owner,
+    Group,
+    Hash,
+    Lines,
+    Code,
+    Comments,
+    Blank,
+    Detected,
+    Git,
+}
+
+impl CsvColumn {
+    pub fn parse(s: &str) -> Option<CsvColumn> {
+        match s {
+            "name" => Some(CsvColumn::Name),
+            "path" | "relpath" | "relative" => Some(CsvColumn::Path),
+            "abspath" | "absolute" => Some(CsvColumn::AbsPath),
+            "parent" => Some(CsvColumn::Parent),
+            "depth" => Some(CsvColumn::Depth),
+            "ext" | "extension" => Some(CsvColumn::Ext),
+            "type" => Some(CsvColumn::Type),
+            "size" => Some(CsvColumn::Size),
+            "mtime" | "time" | "modified" => Some(CsvColumn::Mtime),
+            "mode" | "perms" | "permissions" => Some(CsvColumn::Mode),
+            "owner" | "user" => Some(CsvColumn::Owner),
+            "group" => Some(CsvColumn::Group),
+            "hash" | "digest" => Some(CsvColumn::Hash),
+            "lines" => Some(CsvColumn::Lines),
+            "code" | "loc" => Some(CsvColumn::Code),
+            "comments" => Some(CsvColumn::Comments),
+            "blank" => Some(CsvColumn::Blank),
+            "detected" | "magic" => Some(CsvColumn::Detected),
+            "git" => Some(CsvColumn::Git),
+            _ => None,
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            CsvColumn::Name => "name",
+            CsvColumn::Path => "path",
+            CsvColumn::AbsPath => "abspath",
+            CsvColumn::Parent => "parent",
+            CsvColumn::Depth => "depth",
+            CsvColumn::Ext => "ext",
+            CsvColumn::Type => "type",
+            CsvColumn::Size => "size",
+            CsvColumn::Mtime => "mtime",
+            CsvColumn::Mode => "mode",
+            CsvColumn::Owner => "owner",
+            CsvColumn::Group => "group",
+            CsvColumn::Hash => "hash",
+            CsvColumn::Lines => "lines",
+            CsvColumn::Code => "code",
+            CsvColumn::Comments => "comments",
+            CsvColumn::Blank => "blank",
+            CsvColumn::Detected => "detected",
+            CsvColumn::Git => "git",
+        }
+    }
+
+    /// these only have values when the walk collected metadata
+    pub fn needs_meta(self) -> bool {
+        matches!(self, CsvColumn::Mtime | CsvColumn::Mode | CsvColumn::Owner | CsvColumn::Group)
+    }
+}
+
+// same three columns the csv export always had
+pub const DEFAULT_CSV_COLUMNS: [CsvColumn; 3] = [CsvColumn::Path, CsvColumn::Type, CsvColumn::Size];
+
+// tacked on after the defaults when --loc is given without --columns, --detect and --git add their columns the same way
+pub const LOC_CSV_COLUMNS: [CsvColumn; 4] = [CsvColumn::Lines, CsvColumn::Code, CsvColumn::Comments, CsvColumn::Blank];
+
+pub struct CsvOptions {
+    pub columns: Vec<CsvColumn>,
+    pub delimiter: u8,
+    pub header: bool,
+    /// absolute scan root, for the abspath column
+    pub root: PathBuf,
+}
+
+/// `,` `;` `|` and friends as-is, plus `tab` / `\t` since a literal tab is a pain to type
+pub fn parse_delimiter(s: &str) -> Option<u8> {
+    match s {
+        "tab" | "\\t" | "\t" => Some(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() && s != "\"" && s != "\n" => Some(s.as_bytes()[0]),
+        _ => None,
+    }
+}
+
+pub struct CsvSink<W: Write> {
+    out: csv::Writer<W>,
+    opts: CsvOptions,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(out: W, opts: CsvOptions) -> io::Result<CsvSink<W>> {
+        let mut out = csv::WriterBuilder::new().delimiter(opts.delimiter).from_writer(out);
+        if opts.header {
+            out.write_record(opts.columns.iter().map(|c| c.header()))?;
+        }
+        Ok(CsvSink { out, opts })
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn cell(&self, column: CsvColumn, node: &TreeNode, path: &str, depth: usize) -> String {
+        match column {
+            CsvColumn::Name => node.name.clone(),
+            CsvColumn::Path => if path.is_empty() { ".".to_string() } else { path.to_string() },
+            CsvColumn::AbsPath => {
+                let abs = if path.is_empty() { self.opts.root.clone() } else { self.opts.root.join(path) };
+                abs.to_string_lossy().to_string()
+            }
+            // the root has no parent inside the scan, top level entries hang off "."
+            CsvColumn::Parent => match path.rsplit_once('/') {
+                Some((parent, _)) => parent.to_string(),
+                None if path.is_empty() => String::new(),
+                None => ".".to_string(),
+            },
+            CsvColumn::Depth => depth.to_string(),
+            CsvColumn::Ext => match node.is_dir {
+                true => String::new(),
+                false => Path::new(&node.name).extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default(),
+            },
+            CsvColumn::Type => entry_type(node).to_string(),
+            CsvColumn::Size => node.size.map(|s| s.to_string()).unwrap_or_default(),
+            CsvColumn::Mtime => node.mtime.map(format_time_rfc3339).unwrap_or_default(),
+            CsvColumn::Mode => node.mode.map(|m| format!("{:04o}", m & 0o7777)).unwrap_or_default(),
+            CsvColumn::Owner => node.owner.clone().unwrap_or_default(),
+            CsvColumn::Group => node.group.clone().unwrap_or_default(),
+            CsvColumn::Hash => node.hash.clone().unwrap_or_default(),
+            CsvColumn::Lines => node.loc.map(|l| l.lines.to_string()).unwrap_or_default(),
+            CsvColumn::Code => node.loc.map(|l| l.code.to_string()).unwrap_or_default(),
+            CsvColumn::Comments => node.loc.map(|l| l.comments.to_string()).unwrap_or_default(),
+            CsvColumn::Blank => node.loc.map(|l| l.blank.to_string()).unwrap_or_default(),
+            CsvColumn::Detected => node.detected.clone().unwrap_or_default(),
+            CsvColumn::Git => node.git.map(|s| s.name().to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+impl<W: Write> Sink for CsvSink<W> {
+    fn entry(&mut self, node: &TreeNode, path: &str, depth: usize, _has_children: bool) -> io::Result<()> {
+        let row: Vec<String> = self.opts.columns.iter().map(|&c| self.cell(c, node, path, depth)).collect();
+        self.out.write_record(&row)?;
+        Ok(())
+    }
+
+    fn leave(&mut self, _depth: usize) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn entry_type(node: &TreeNode) -> &'static str {
+    match (node.is_dir, node.special) {
+        (true, _) => "directory",
+        (false, Some(special)) => special.name(),
+        (false, None) => "file",
+    }
+}
+
+// databases parse this without a format string, unlike the tree's short iso style
+fn format_time_rfc3339(secs: i64) -> String {
+    use chrono::{Local, TimeZone};
+    match Local.timestamp_opt(secs, 0).single() {
+        Some(t) => t.to_rfc3339(),
+        None => String::new(),
+    }
+}
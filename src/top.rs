@@ -0,0 +1,243 @@
+use crate::meta::format_time_iso;
+use crate::stream::Sink;
+use crate::{SizeStyle, TreeNode};
+use colored::Colorize;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io;
+
+// canopy top: the biggest, fullest and oldest things in a scan. it rides the streaming walker
+// and only ever keeps n entries per list, so a scan of millions of files stays small
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TopBy {
+    Size,
+    Count,
+    Mtime,
+}
+
+impl TopBy {
+    pub fn parse(s: &str) -> Option<TopBy> {
+        match s {
+            "size" => Some(TopBy::Size),
+            "count" | "entries" => Some(TopBy::Count),
+            "mtime" | "time" | "age" => Some(TopBy::Mtime),
+            _ => None,
+        }
+    }
+}
+
+/// keeps the n largest keys seen so far, a min-heap so the smallest one is the one to beat
+struct TopN<K: Ord, V: Ord> {
+    n: usize,
+    heap: BinaryHeap<Reverse<(K, V)>>,
+}
+
+impl<K: Ord, V: Ord> TopN<K, V> {
+    fn new(n: usize) -> TopN<K, V> {
+        TopN { n, heap: BinaryHeap::with_capacity(n + 1) }
+    }
+
+    fn push(&mut self, key: K, value: V) {
+        if self.n == 0 {
+            return;
+        }
+        if self.heap.len() < self.n {
+            self.heap.push(Reverse((key, value)));
+        } else if self.heap.peek().is_some_and(|Reverse((min, _))| key > *min) {
+            self.heap.pop();
+            self.heap.push(Reverse((key, value)));
+        }
+    }
+
+    /// biggest first
+    fn into_sorted(self) -> Vec<(K, V)> {
+        let mut items: Vec<(K, V)> = self.heap.into_iter().map(|Reverse(kv)| kv).collect();
+        items.sort_by(|a, b| b.cmp(a));
+        items
+    }
+}
+
+struct OpenDir {
+    path: String,
+    size: u64,
+    entries: u64,
+    total: u64,
+}
+
+pub struct TopSink {
+    by: TopBy,
+    open: Vec<OpenDir>,
+    files_by_size: TopN<u64, String>,
+    dirs_by_size: TopN<u64, String>,
+    dirs_by_count: TopN<(u64, u64), String>,
+    newest: TopN<i64, String>,
+    oldest: TopN<Reverse<i64>, String>,
+}
+
+impl TopSink {
+    pub fn new(by: TopBy, n: usize) -> TopSink {
+        TopSink {
+            by,
+            open: Vec::new(),
+            files_by_size: TopN::new(n),
+            dirs_by_size: TopN::new(n),
+            dirs_by_count: TopN::new(n),
+            newest: TopN::new(n),
+            oldest: TopN::new(n),
+        }
+    }
+
+    fn close_dir(&mut self, dir: OpenDir) {
+        if let Some(parent) = self.open.last_mut() {
+            parent.size += dir.size;
+            parent.total += dir.total;
+        }
+        let path = if dir.path.is_empty() { ".".to_string() } else { dir.path };
+        self.dirs_by_size.push(dir.size, path.clone());
+        self.dirs_by_count.push((dir.entries, dir.total), path);
+    }
+}
+
+impl Sink for TopSink {
+    fn entry(&mut self, node: &TreeNode, path: &str, _depth: usize, has_children: bool) -> io::Result<()> {
+        if let Some(parent) = self.open.last_mut() {
+            parent.entries += 1;
+            parent.total += 1;
+        }
+        if node.is_dir {
+            let dir = OpenDir { path: path.to_string(), size: 0, entries: 0, total: 0 };
+            // empty folders never get a leave, they're done right away
+            if has_children {
+                self.open.push(dir);
+            } else {
+                self.close_dir(dir);
+            }
+            return Ok(());
+        }
+        let size = node.size.unwrap_or(0);
+        if let Some(parent) = self.open.last_mut() {
+            parent.size += size;
+        }
+        self.files_by_size.push(size, path.to_string());
+        if let (TopBy::Mtime, Some(mtime)) = (self.by, node.mtime) {
+            self.newest.push(mtime, path.to_string());
+            self.oldest.push(Reverse(mtime), path.to_string());
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, _depth: usize) -> io::Result<()> {
+        if let Some(dir) = self.open.pop() {
+            self.close_dir(dir);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct SizeRow {
+    path: String,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct CountRow {
+    path: String,
+    entries: u64,
+    total: u64,
+}
+
+#[derive(Serialize)]
+struct TimeRow {
+    path: String,
+    mtime: i64,
+}
+
+#[derive(Serialize, Default)]
+struct Report {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    largest_files: Option<Vec<SizeRow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    largest_dirs: Option<Vec<SizeRow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    most_entries: Option<Vec<CountRow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    newest_files: Option<Vec<TimeRow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    oldest_files: Option<Vec<TimeRow>>,
+}
+
+impl TopSink {
+    fn report(self) -> Report {
+        let sizes = |t: TopN<u64, String>| t.into_sorted().into_iter().map(|(size, path)| SizeRow { path, size }).collect();
+        match self.by {
+            TopBy::Size => Report {
+                largest_files: Some(sizes(self.files_by_size)),
+                largest_dirs: Some(sizes(self.dirs_by_size)),
+                ..Default::default()
+            },
+            TopBy::Count => Report {
+                most_entries: Some(
+                    self.dirs_by_count
+                        .into_sorted()
+                        .into_iter()
+                        .map(|((entries, total), path)| CountRow { path, entries, total })
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+            TopBy::Mtime => Report {
+                newest_files: Some(self.newest.into_sorted().into_iter().map(|(mtime, path)| TimeRow { path, mtime }).collect()),
+                oldest_files: Some(
+                    self.oldest
+                        .into_sorted()
+                        .into_iter()
+                        .map(|(Reverse(mtime), path)| TimeRow { path, mtime })
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn export_json(self) -> io::Result<()> {
+        println!("{}", serde_json::to_string_pretty(&self.report())?);
+        Ok(())
+    }
+
+    pub fn print_table(self, sizes: &SizeStyle) {
+        let report = self.report();
+        let mut first = true;
+        let mut heading = |title: &str, columns: &str| {
+            if !first {
+                println!();
+            }
+            first = false;
+            println!("{}", title.bold());
+            println!("{}", columns.dimmed());
+        };
+        for (title, rows) in [("Largest files", report.largest_files), ("Largest directories", report.largest_dirs)] {
+            let Some(rows) = rows else { continue };
+            heading(title, &format!("{:>10}  PATH", "SIZE"));
+            for row in rows {
+                let size = format!("{:>10}", sizes.format(row.size));
+                println!("{}  {}", size.color(sizes.color(row.size)), row.path);
+            }
+        }
+        if let Some(rows) = report.most_entries {
+            heading("Most entries", &format!("{:>10}  {:>10}  PATH", "ENTRIES", "TOTAL"));
+            for row in rows {
+                println!("{:>10}  {:>10}  {}", row.entries, row.total, row.path);
+            }
+        }
+        for (title, rows) in [("Newest files", report.newest_files), ("Oldest files", report.oldest_files)] {
+            let Some(rows) = rows else { continue };
+            heading(title, &format!("{:<16}  PATH", "MODIFIED"));
+            for row in rows {
+                println!("{:<16}  {}", format_time_iso(row.mtime), row.path);
+            }
+        }
+    }
+}